@@ -0,0 +1,331 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::{error, info, warn};
+
+use crate::config::Portfolio;
+use crate::state::AppState;
+use crate::storage::save_alerts;
+use crate::sources::CreditsSource;
+use crate::utilities::AttestationResult;
+use crate::webhooks::{emit, WebhookEvent};
+
+/// A condition to alert on, as configured in the config file.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum AlertRule {
+    /// The attested total of the asset is below the floor, in its smallest unit.
+    Floor { asset: String, floor: u64 },
+    /// The attested total dropped by more than the percentage since the previous attestation.
+    /// Applies to every asset if none is given.
+    Drop { asset: Option<String>, percent: f64 },
+    /// A tracked address that held credits at the previous attestation holds none.
+    AddressZero,
+    /// No attestation has been accepted for this many hours.
+    Stale { hours: u64 },
+}
+
+/// An alert that is raised and not cleared yet.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Alert {
+    /// Identifies the rule and what it fired on, e.g. `floor:credits`.
+    pub id: String,
+    pub message: String,
+    /// Unix time the alert was raised.
+    pub raised: i64,
+}
+
+/// What the rules are evaluated against, kept across restarts.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AlertState {
+    /// The total of each asset at its last accepted attestation over every tracked address.
+    pub totals: HashMap<String, u128>,
+    /// The total of each asset at its last accepted attestation over a portfolio, by portfolio
+    /// name, so that it is not compared with the total over every address.
+    #[serde(default)]
+    pub portfolio_totals: HashMap<String, HashMap<String, u128>>,
+    /// The credits balance of each address at the last accepted credits attestation.
+    pub balances: HashMap<String, u64>,
+    /// Unix time of the last accepted attestation.
+    pub last_accepted: Option<i64>,
+    pub active: Vec<Alert>,
+}
+
+impl AlertState {
+    /// The totals at the last accepted attestation over the portfolio, or over every address.
+    fn totals_of(&mut self, portfolio: Option<&str>) -> &mut HashMap<String, u128> {
+        match portfolio {
+            Some(portfolio) => self.portfolio_totals.entry(portfolio.to_string()).or_default(),
+            None => &mut self.totals,
+        }
+    }
+}
+
+/// What an accepted attestation showed.
+#[derive(Default)]
+pub struct Observation {
+    /// The attested total of each asset the attestation records.
+    pub totals: Vec<(String, u128)>,
+    /// The credits balance of each tracked address, read when an address rule or a portfolio is configured.
+    pub balances: Option<HashMap<String, u64>>,
+    /// Whether the attestation is of the reserves, as opposed to a token, off-Aleo source,
+    /// private records or the liabilities.
+    pub reserves: bool,
+    /// The portfolio the attestation covers, for a token asset attested over just its addresses.
+    pub portfolio: Option<String>,
+}
+
+/// The alert ID of a rule firing on an asset, e.g. `drop:credits` or `drop:cold-storage:token:1field`.
+fn scoped_id(rule: &str, portfolio: Option<&str>, asset: &str) -> String {
+    match portfolio {
+        Some(portfolio) => format!("{}:{}:{}", rule, portfolio, asset),
+        None => format!("{}:{}", rule, asset),
+    }
+}
+
+/// How an asset is named in the alert messages, with the portfolio it was attested over.
+fn scoped_asset(portfolio: Option<&str>, asset: &str) -> String {
+    match portfolio {
+        Some(portfolio) => format!("{} (portfolio {})", asset, portfolio),
+        None => asset.to_string(),
+    }
+}
+
+/// Reads the address balances for the accepted attestation, if the rules or the per-portfolio
+/// history need them.
+pub fn observe(
+    result: Option<&AttestationResult>,
+    addresses: &[String],
+    rules: &[AlertRule],
+    portfolios: &[Portfolio],
+    endpoint: &str,
+    network: &str,
+) -> Observation {
+    let totals = result.map(|result| result.totals()).unwrap_or_default();
+    let credits = totals.iter().any(|(asset, _)| asset == "credits");
+    let balances = (credits && (rules.contains(&AlertRule::AddressZero) || !portfolios.is_empty()))
+        .then(|| read_balances(addresses, endpoint, network));
+    Observation { totals, balances, reserves: false, portfolio: None }
+}
+
+/// Reads the public credits balance of each address, skipping the ones that fail.
+fn read_balances(addresses: &[String], endpoint: &str, network: &str) -> HashMap<String, u64> {
+    let credits = CreditsSource { endpoint: endpoint.to_string(), network: network.to_string(), addresses: addresses.to_vec() };
+    credits.addresses.iter()
+        .filter_map(|address| match credits.balance(address) {
+            Ok(balance) => Some((address.clone(), balance)),
+            Err(e) => {
+                warn!("{} for the alert rules", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Evaluates the rules after an attestation resolves, or with no observation to only check for
+/// staleness. Alerts are raised once, and cleared when their condition no longer holds.
+pub async fn evaluate(st: &mut AppState, observation: Option<Observation>) {
+    let now = Utc::now();
+    let accepted = observation.is_some();
+    let rules = st.alert_rules.clone();
+    let checks = check_rules(&mut st.alerts, &rules, observation, now.timestamp(), st.started.timestamp());
+
+    let mut changed = accepted;
+    for (id, message) in checks {
+        let active = st.alerts.active.iter().position(|alert| alert.id == id);
+        match (message, active) {
+            (Some(message), None) => {
+                warn!("Alert raised: {}", message);
+                st.alerts.active.push(Alert { id: id.clone(), message: message.clone(), raised: now.timestamp() });
+                emit(st, WebhookEvent::AlertRaised { alert: id, message }).await;
+                changed = true;
+            }
+            (None, Some(index)) => {
+                let alert = st.alerts.active.remove(index);
+                info!("Alert cleared: {}", alert.message);
+                emit(st, WebhookEvent::AlertCleared { alert: id, message: alert.message }).await;
+                changed = true;
+            }
+            _ => {}
+        }
+    }
+
+    if changed {
+        if let Err(e) = save_alerts(&st.alerts, &st.alerts_file).await {
+            error!("Failed to save alerts: {}", e);
+        }
+    }
+}
+
+/// Checks the rules against an accepted attestation, or only for staleness with no observation,
+/// and remembers the attestation for the next comparison. Each check is an alert ID, with a
+/// message if its condition holds.
+fn check_rules(
+    alerts: &mut AlertState,
+    rules: &[AlertRule],
+    observation: Option<Observation>,
+    now: i64,
+    started: i64,
+) -> Vec<(String, Option<String>)> {
+    let mut checks: Vec<(String, Option<String>)> = Vec::new();
+    let portfolio = observation.as_ref().and_then(|observation| observation.portfolio.clone());
+    let portfolio = portfolio.as_deref();
+    // Only the reserves attestation keeps the tracker fresh; the token, external, private and
+    // liabilities attestations do not.
+    if observation.as_ref().is_some_and(|observation| observation.reserves) {
+        alerts.last_accepted = Some(now);
+    }
+
+    for rule in rules {
+        match rule {
+            AlertRule::Stale { hours } => {
+                let since = alerts.last_accepted.unwrap_or(started);
+                let stale = now - since > *hours as i64 * 3600;
+                checks.push(("stale".to_string(), stale.then(|| {
+                    format!("No attestation has been accepted in the last {}h", hours)
+                })));
+            }
+            AlertRule::Floor { asset, floor } => {
+                let totals = observation.iter().flat_map(|observation| &observation.totals);
+                for (observed, total) in totals {
+                    if observed == asset {
+                        checks.push((scoped_id("floor", portfolio, asset), (*total < *floor as u128).then(|| {
+                            format!("Attested {} total {} is below the floor of {}", scoped_asset(portfolio, asset), total, floor)
+                        })));
+                    }
+                }
+            }
+            AlertRule::Drop { asset, percent } => {
+                let totals = observation.iter().flat_map(|observation| &observation.totals);
+                for (observed, total) in totals {
+                    if asset.as_ref().is_none_or(|asset| asset == observed) {
+                        let dropped = alerts.totals_of(portfolio).get(observed)
+                            .filter(|previous| **previous > 0 && total < *previous)
+                            .map(|previous| (*previous, (previous - total) as f64 / *previous as f64 * 100.0))
+                            .filter(|(_, drop)| drop > percent);
+                        checks.push((scoped_id("drop", portfolio, observed), dropped.map(|(previous, drop)| {
+                            format!("Attested {} total dropped {:.2}% from {} to {}", scoped_asset(portfolio, observed), drop, previous, total)
+                        })));
+                    }
+                }
+            }
+            AlertRule::AddressZero => {
+                if let Some(Observation { balances: Some(balances), .. }) = &observation {
+                    for (address, balance) in balances {
+                        let id = format!("address_zero:{}", address);
+                        let fell = *balance == 0 && alerts.balances.get(address).is_some_and(|previous| *previous > 0);
+                        let still = *balance == 0 && alerts.active.iter().any(|alert| alert.id == id);
+                        checks.push((id, (fell || still).then(|| format!("Address {} fell to zero", address))));
+                    }
+                    // Addresses that are no longer tracked cannot stay alerted.
+                    for alert in alerts.active.iter().filter(|alert| alert.id.starts_with("address_zero:")) {
+                        if !balances.contains_key(&alert.id["address_zero:".len()..]) {
+                            checks.push((alert.id.clone(), None));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Remember this attestation for the next comparison.
+    if let Some(observation) = observation {
+        alerts.totals_of(portfolio).extend(observation.totals);
+        if let Some(balances) = observation.balances {
+            alerts.balances = balances;
+        }
+    }
+
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 3600;
+
+    fn reserves(totals: &[(&str, u128)]) -> Observation {
+        Observation {
+            totals: totals.iter().map(|(asset, total)| (asset.to_string(), *total)).collect(),
+            balances: None,
+            reserves: true,
+            portfolio: None,
+        }
+    }
+
+    fn raised(checks: &[(String, Option<String>)]) -> Vec<&str> {
+        checks.iter().filter(|(_, message)| message.is_some()).map(|(id, _)| id.as_str()).collect()
+    }
+
+    #[test]
+    fn only_reserves_attestations_keep_the_tracker_fresh() {
+        let rules = [AlertRule::Stale { hours: 1 }];
+        let mut alerts = AlertState::default();
+        // Two hours after startup with nothing accepted, the tracker is stale.
+        assert_eq!(raised(&check_rules(&mut alerts, &rules, None, 2 * HOUR, 0)), ["stale"]);
+        // A liabilities or token attestation does not clear it.
+        let liabilities = Observation::default();
+        assert_eq!(raised(&check_rules(&mut alerts, &rules, Some(liabilities), 2 * HOUR, 0)), ["stale"]);
+        assert_eq!(alerts.last_accepted, None);
+        let token = Observation { reserves: false, ..reserves(&[("token:1field", 5)]) };
+        assert_eq!(raised(&check_rules(&mut alerts, &rules, Some(token), 2 * HOUR, 0)), ["stale"]);
+        // A reserves attestation does, until an hour passes again.
+        assert!(raised(&check_rules(&mut alerts, &rules, Some(reserves(&[("credits", 5)])), 2 * HOUR, 0)).is_empty());
+        assert_eq!(alerts.last_accepted, Some(2 * HOUR));
+        assert!(raised(&check_rules(&mut alerts, &rules, None, 3 * HOUR, 0)).is_empty());
+        assert_eq!(raised(&check_rules(&mut alerts, &rules, None, 3 * HOUR + 1, 0)), ["stale"]);
+    }
+
+    #[test]
+    fn floor_fires_below_the_floor_of_its_asset() {
+        let rules = [AlertRule::Floor { asset: "credits".to_string(), floor: 100 }];
+        let mut alerts = AlertState::default();
+        let checks = check_rules(&mut alerts, &rules, Some(reserves(&[("credits", 99), ("BTC", 1)])), 0, 0);
+        assert_eq!(raised(&checks), ["floor:credits"]);
+        let checks = check_rules(&mut alerts, &rules, Some(reserves(&[("credits", 100)])), 0, 0);
+        assert_eq!(checks, [("floor:credits".to_string(), None)]);
+    }
+
+    #[test]
+    fn drop_compares_with_the_previous_total() {
+        let rules = [AlertRule::Drop { asset: None, percent: 10.0 }];
+        let mut alerts = AlertState::default();
+        // The first attestation has nothing to compare with.
+        assert!(raised(&check_rules(&mut alerts, &rules, Some(reserves(&[("credits", 1000)])), 0, 0)).is_empty());
+        assert!(raised(&check_rules(&mut alerts, &rules, Some(reserves(&[("credits", 900)])), 0, 0)).is_empty());
+        let checks = check_rules(&mut alerts, &rules, Some(reserves(&[("credits", 800)])), 0, 0);
+        assert_eq!(raised(&checks), ["drop:credits"]);
+        assert_eq!(alerts.totals["credits"], 800);
+    }
+
+    #[test]
+    fn drop_compares_a_portfolio_total_with_the_same_portfolio() {
+        let rules = [AlertRule::Drop { asset: None, percent: 10.0 }];
+        let mut alerts = AlertState::default();
+        let token = |total: u128| Observation { reserves: false, ..reserves(&[("token:1field", total)]) };
+        let cold = |total: u128| Observation { portfolio: Some("cold".to_string()), ..token(total) };
+        // The global attestation covers every address, so its total is far above the portfolio's.
+        assert!(raised(&check_rules(&mut alerts, &rules, Some(token(1000)), 0, 0)).is_empty());
+        assert!(raised(&check_rules(&mut alerts, &rules, Some(cold(100)), 0, 0)).is_empty());
+        assert!(raised(&check_rules(&mut alerts, &rules, Some(token(1000)), 0, 0)).is_empty());
+        assert!(raised(&check_rules(&mut alerts, &rules, Some(cold(100)), 0, 0)).is_empty());
+        assert_eq!(alerts.totals["token:1field"], 1000);
+        assert_eq!(alerts.portfolio_totals["cold"]["token:1field"], 100);
+        // A drop within the portfolio still fires, under its own ID.
+        assert_eq!(raised(&check_rules(&mut alerts, &rules, Some(cold(50)), 0, 0)), ["drop:cold:token:1field"]);
+    }
+
+    #[test]
+    fn address_zero_fires_when_a_funded_address_empties() {
+        let rules = [AlertRule::AddressZero];
+        let mut alerts = AlertState::default();
+        let observe = |balance: u64| Observation {
+            balances: Some(HashMap::from([("aleo1a".to_string(), balance)])),
+            ..reserves(&[])
+        };
+        assert!(raised(&check_rules(&mut alerts, &rules, Some(observe(0)), 0, 0)).is_empty());
+        assert!(raised(&check_rules(&mut alerts, &rules, Some(observe(5)), 0, 0)).is_empty());
+        assert_eq!(raised(&check_rules(&mut alerts, &rules, Some(observe(0)), 0, 0)), ["address_zero:aleo1a"]);
+    }
+}
@@ -1,19 +1,64 @@
 use std::str::FromStr;
 use snarkvm::prelude::store::ConsensusStore;
 use snarkvm::prelude::store::helpers::memory::ConsensusMemory;
-use snarkvm::prelude::{Address, Plaintext, Literal, PrivateKey, Program, Value, VM, Transaction};
+use snarkvm::prelude::{Address, Field, Input, Plaintext, Literal, PrivateKey, Program, ProgramID, Record, Value, VM, Transaction, U64, Zero};
 use snarkvm::prelude::query::Query;
 use tokio::sync::mpsc;
-use tokio::time::{interval, Duration};
+use std::time::Instant;
+use tokio::time::{interval, interval_at, Duration, MissedTickBehavior};
 use tokio::task::spawn_blocking;
 
-use crate::{CurrentAleo, CurrentNetwork, state::AppState, state::VM_GLOBAL};
+use crate::{CurrentAleo, CurrentNetwork, LEGACY_PROGRAM_ID, NETWORK_NAME, PROGRAM_ID, SOLVENCY_PROGRAM_ID, TOKEN_PROGRAM_ID, state::AppState, state::Mode, state::JobStatus, state::LiabilityTransaction, state::PendingTransaction, state::TrackedTransaction, state::TransactionKind, state::Resolution, state::RunOutcome, state::VM_GLOBAL};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use chrono::Utc;
 use tokio::sync::{RwLock};
-use crate::utilities::broadcast_transaction;
+use crate::metrics::METRICS;
+use crate::telemetry::run_id;
+use tracing::{error, info, info_span, warn, Instrument, Span};
+use crate::utilities::{broadcast_transaction, get_attestation_details, get_block_height, get_confirmed_transaction, get_program};
+use crate::webhooks::{emit, WebhookEvent};
+use crate::alerts::{evaluate, observe, Observation};
+use crate::config::tag_totals;
+use crate::reports::AttestationReport;
+use crate::evidence::{check_attestation, export_bundle};
+use crate::prices::{valued_totals, RecordedPrice};
+use crate::records::{fresh_records, scan_records};
+use crate::sources::{asset_field, hash_addresses, BalanceSource, CreditsSource, SourceTotal};
+
+/// How many off-Aleo sources one `record_external_balances` attestation commits.
+pub const MAX_EXTERNAL_SOURCES: usize = 8;
+/// How often the pending transactions are checked for confirmation.
+const CONFIRMATION_INTERVAL: Duration = Duration::from_secs(30);
+/// How long a broadcast transaction may stay unconfirmed before it counts as rejected.
+const PENDING_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// Whether the background task is running. Cleared when the task exits, even by panicking.
+static TASK_ALIVE: AtomicBool = AtomicBool::new(false);
+
+struct AliveGuard;
+
+impl Drop for AliveGuard {
+    fn drop(&mut self) {
+        TASK_ALIVE.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Whether the background task is still running.
+pub fn task_alive() -> bool {
+    TASK_ALIVE.load(Ordering::SeqCst)
+}
+
+/// Whether the VM is initialized with the programs deployed. The VM is only stored once
+/// every program has been deployed, so this does not need to take its lock.
+pub fn vm_ready() -> bool {
+    VM_GLOBAL.get().is_some()
+}
 
 pub enum BackgroundTaskMsg {
     RunNow,
+    /// Changes the seconds between attestations, counting the new period from the last scheduled run.
+    SetCadence(u64),
     Shutdown,
 }
 
@@ -23,20 +68,35 @@ pub fn spawn_background_task(
     app_state: Arc<RwLock<AppState>>,
 ) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
+        TASK_ALIVE.store(true, Ordering::SeqCst);
+        let _alive = AliveGuard;
+
+        // Deploy the programs up front, so readiness does not wait on the first attestation.
+        match spawn_blocking(|| get_or_init_vm().map(|_| ())).await {
+            Ok(Ok(())) => info!("VM initialized."),
+            Ok(Err(e)) => error!("Failed to initialize VM: {}", e),
+            Err(e) => error!("Failed to initialize VM: {:?}", e),
+        }
+
         let mut ticker = interval(Duration::from_secs(cadence));
+        let mut last_scheduled = tokio::time::Instant::now();
         loop {
             tokio::select! {
-                _ = ticker.tick() => {
-                    if let Err(e) = prove_public_balance(&app_state).await {
-                        eprintln!("Failed to run background task: {}", e);
-                    }
+                scheduled = ticker.tick() => {
+                    last_scheduled = scheduled;
+                    run_attestation(&app_state).await;
                 }
                 msg = rx.recv() => {
                     match msg {
                         Some(BackgroundTaskMsg::RunNow) => {
-                            if let Err(e) = prove_public_balance(&app_state).await {
-                                eprintln!("Failed to run background task: {}", e);
-                            }
+                            run_attestation(&app_state).await;
+                        }
+                        Some(BackgroundTaskMsg::SetCadence(cadence)) => {
+                            // A shorter cadence that is already overdue runs once now, not once per missed period.
+                            let period = Duration::from_secs(cadence);
+                            ticker = interval_at(last_scheduled + period, period);
+                            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                            info!("Background task cadence set to {}s", cadence);
                         }
                         Some(BackgroundTaskMsg::Shutdown) | None => {
                             // Exit the loop on shutdown or channel closed
@@ -46,102 +106,758 @@ pub fn spawn_background_task(
                 }
             }
         }
-        println!("Background task shutting down gracefully.");
+        info!("Background task shutting down gracefully.");
     })
 }
 
-async fn prove_public_balance(app_state: &Arc<RwLock<AppState>>) -> Result<(), String> {
+async fn run_attestation(app_state: &Arc<RwLock<AppState>>) {
+    let run_id = run_id();
+    let span = info_span!("attestation", run_id = %run_id);
+    async {
+        // The run takes up every job queued so far.
+        let tracked_before = {
+            let mut st = app_state.write().await;
+            let started = Utc::now().timestamp();
+            for job in st.jobs.iter_mut().filter(|job| job.status == JobStatus::Queued) {
+                job.status = JobStatus::Running;
+                job.started = Some(started);
+                job.run_id = Some(run_id.clone());
+            }
+            st.transactions.len()
+        };
+        let result = prove_public_balance(app_state, &run_id).await;
+        METRICS.record_attestation(result.is_ok());
+        let mut st = app_state.write().await;
+        let broadcast = st.transactions.get(tracked_before..).unwrap_or_default()
+            .iter().map(|tracked| tracked.id.clone()).collect::<Vec<_>>();
+        for job in st.jobs.iter_mut().filter(|job| job.run_id.as_ref() == Some(&run_id)) {
+            job.status = if result.is_ok() { JobStatus::Succeeded } else { JobStatus::Failed };
+            job.finished = Some(Utc::now().timestamp());
+            job.error = result.as_ref().err().cloned();
+            job.transactions = broadcast.clone();
+        }
+        if let Err(e) = &result {
+            error!("Failed to run background task: {}", e);
+            emit(&mut st, WebhookEvent::ProvingFailed { run_id: run_id.clone(), error: e.clone() }).await;
+        }
+        let finished = Utc::now();
+        if result.is_ok() {
+            st.last_success = Some(finished);
+        }
+        st.last_run = Some(RunOutcome { finished, error: result.err() });
+    }
+    .instrument(span)
+    .await
+}
+
+async fn prove_public_balance(app_state: &Arc<RwLock<AppState>>, run_id: &str) -> Result<(), String> {
     let st = app_state.read().await;
-    let private_key = PrivateKey::<CurrentNetwork>::from_str(&st.private_key)
-        .map_err(|e| format!("Failed to parse private key: {}", e))?;
+    let (private_key, addresses) = info_span!("parse_inputs").in_scope(|| {
+        let private_key = PrivateKey::<CurrentNetwork>::from_str(&st.private_key)
+            .map_err(|e| format!("Failed to parse private key: {}", e))?;
 
-    let addresses = st.addresses.iter()
-        .map(|addr_str| Address::<CurrentNetwork>::from_str(addr_str)
-            .map_err(|e| format!("Failed to parse address '{}': {}", addr_str, e))
-        )
-        .collect::<Result<Vec<_>, _>>()?;
+        let addresses = parse_addresses(&st.addresses)?;
+        Ok::<_, String>((private_key, addresses))
+    })?;
 
     let endpoint = st.endpoint.clone();
     let transactions_file = st.transactions_file.clone(); // Add this field to AppState
+    let liability_transactions_file = st.liability_transactions_file.clone();
+    let liabilities = st.liabilities.as_ref().map(|tree| (tree.root(), tree.total()));
+    let view_keys = st.view_keys.clone();
+    let mut records = st.records.clone();
+    let records_file = st.records_file.clone();
+    // In solvency mode, the threshold defaults to the committed liabilities total.
+    let threshold = match st.mode {
+        Mode::Reserves => None,
+        Mode::Solvency => Some(st.solvency_threshold
+            .or(liabilities.map(|(_, total)| total))
+            .ok_or_else(|| "Solvency mode requires a threshold or committed liabilities".to_string())?),
+    };
+
+    let tracked = st.addresses.clone();
+    let no_addresses = Vec::new();
+    let include_stake = st.include_stake;
+    let priority_fee = st.priority_fee;
+    // The assets in the list are attested over every tracked address, and the assets of a
+    // portfolio over the addresses of that portfolio.
+    let assets = st.assets.iter()
+        .map(|token_id| (token_id.clone(), st.addresses.clone()))
+        .chain(st.portfolios.iter().flat_map(|portfolio| {
+            portfolio.assets.iter().map(|token_id| (token_id.clone(), portfolio.addresses.clone()))
+        }))
+        .collect::<Vec<_>>();
+    let sources = st.sources.iter().map(|source| source.build()).collect::<Vec<_>>();
+    let tracked_addresses = tracked.clone();
 
     drop(st);
 
-    // Run CPU-intensive in blocking
-    let transaction = spawn_blocking(move || {
-        let tx = prove_for_address(addresses, private_key, &endpoint)?;
+    // Run CPU-intensive in blocking, staying inside the run's span
+    let span = Span::current();
+    let (transaction, asset_transactions, external_transactions, source_totals, private_transactions, records, liability_transaction, failures) = spawn_blocking(move || {
+        let _guard = span.enter();
+        // The parts of the run that fail without failing the whole run.
+        let mut failures = Vec::new();
+        let tx = prove_for_address(addresses.clone(), private_key, &endpoint, threshold, include_stake, priority_fee)?;
+        // Attest each declared token asset separately, so there is one total per asset.
+        let mut asset_txs = Vec::new();
+        for (token_id, covered) in &assets {
+            let result = parse_addresses(covered)
+                .and_then(|covered| prove_token_balances(covered, token_id, private_key, &endpoint, priority_fee));
+            match result {
+                Ok(asset_tx) => asset_txs.push((asset_tx, covered.clone())),
+                Err(e) => {
+                    warn!("Failed to prove balances of token {}: {}", token_id, e);
+                    failures.push(format!("Failed to prove balances of token {}: {}", token_id, e));
+                }
+            }
+        }
+        // Read every source, the Aleo credits first. The program reads the credits mapping itself, so
+        // only the off-Aleo totals are committed, together, so the same run covers every chain in one
+        // attestation. A source that fails to read is left out of it.
+        let mut external_txs = Vec::new();
+        let credits = CreditsSource { endpoint: endpoint.clone(), network: NETWORK_NAME.to_string(), addresses: tracked_addresses };
+        let source_totals = std::iter::once(&credits as &dyn BalanceSource)
+            .chain(sources.iter().map(|source| source.as_ref()))
+            .map(|source| SourceTotal {
+                asset: source.asset().to_string(),
+                addresses: source.addresses().len(),
+                total: source.total_balance(),
+            })
+            .collect::<Vec<_>>();
+        let read = sources.iter().zip(&source_totals[1..])
+            .filter_map(|(source, read)| Some((source.as_ref(), *read.total.as_ref().ok()?)))
+            .collect::<Vec<_>>();
+        if !read.is_empty() {
+            let covered = read.iter().flat_map(|(source, _)| source.addresses().to_vec()).collect::<Vec<_>>();
+            match prove_external_balances(&read, private_key, &endpoint, priority_fee) {
+                Ok(external_tx) => external_txs.push((external_tx, covered)),
+                Err(e) => {
+                    warn!("Failed to commit external balances: {}", e);
+                    failures.push(format!("Failed to commit external balances: {}", e));
+                }
+            }
+        }
+        // Count the private records found with the view keys. The public attestation is already
+        // broadcast at this point, so a failure here is logged rather than failing the run.
+        let mut private_txs = Vec::new();
+        if !view_keys.is_empty() {
+            let result = scan_records(&mut records, &view_keys, &endpoint, NETWORK_NAME)
+                .and_then(|_| fresh_records(&mut records, &private_key, &endpoint, NETWORK_NAME))
+                .and_then(|fresh| prove_private_records(fresh, private_key, &endpoint, priority_fee));
+            match result {
+                Ok(txs) => private_txs = txs,
+                Err(e) => {
+                    warn!("Failed to prove private records: {}", e);
+                    failures.push(format!("Failed to prove private records: {}", e));
+                }
+            }
+        }
+        // Anchor the liabilities in the same run, so both sides land at comparable heights.
+        // The reserves are already broadcast, so a failure here does not fail the run either.
+        let liability_tx = liabilities.and_then(|(root, total)| {
+            prove_liabilities(root, total, private_key, &endpoint, priority_fee)
+                .map_err(|e| {
+                    warn!("Failed to commit liabilities: {}", e);
+                    failures.push(format!("Failed to commit liabilities: {}", e));
+                })
+                .ok()
+        });
         // If broadcasting should happen inside blocking:
         // vm.broadcast(&tx).map_err(|e| format!("Failed to broadcast: {}", e))?;
-        Ok::<_, String>(tx)
+        Ok::<_, String>((tx, asset_txs, external_txs, source_totals, private_txs, records, liability_tx, failures))
     })
         .await
         .map_err(|e| format!("task join error: {:?}", e))??;
 
-    println!("Background task completed. Transaction ID: {}", transaction.id());
+    info!("Background task completed. Transaction ID: {}", transaction.id());
 
     // If broadcasting is I/O-bound, do it here (non-blocking)
     // broadcast_transaction(&endpoint, &transaction).await.map_err(|e| format!("Failed to broadcast: {}", e))?;
 
     // Store the transaction ID in state and save
-    {
+    async {
         let mut st = app_state.write().await;
-        st.transactions.push(transaction.id().to_string());
-        // Save transactions
-        crate::storage::save_transactions(&st.transactions, &transactions_file).await
-            .map_err(|e| format!("Failed to save transactions: {}", e))?;
+        st.transactions.push(TrackedTransaction::new(transaction.id(), TransactionKind::Reserves));
+        for (asset_transaction, _) in &asset_transactions {
+            st.transactions.push(TrackedTransaction::new(asset_transaction.id(), TransactionKind::Token));
+        }
+        for (external_transaction, _) in &external_transactions {
+            st.transactions.push(TrackedTransaction::new(external_transaction.id(), TransactionKind::External));
+        }
+        for private_transaction in &private_transactions {
+            st.transactions.push(TrackedTransaction::new(private_transaction.id(), TransactionKind::Private));
+        }
+        // Every broadcast transaction is kept in state even if a save fails, and the first
+        // failure to save is reported once the rest is stored.
+        let mut saved = crate::storage::save_transactions(&st.transactions, &transactions_file).await
+            .map_err(|e| format!("Failed to save transactions: {}", e));
+
+        st.source_totals = source_totals;
+        st.records = records;
+        saved = saved.and(crate::storage::save_records(&st.records, &records_file).await
+            .map_err(|e| format!("Failed to save records: {}", e)));
+
+        if let Some(liability_transaction) = &liability_transaction {
+            st.liability_transactions.push(LiabilityTransaction {
+                id: liability_transaction.id().to_string(),
+                reserves: Some(transaction.id().to_string()),
+            });
+            saved = saved.and(crate::storage::save_transactions(&st.liability_transactions, &liability_transactions_file).await
+                .map_err(|e| format!("Failed to save liability transactions: {}", e)));
+        }
+
+        // Watch every broadcast transaction until it is confirmed, and notify the webhooks.
+        let broadcast = std::iter::once((&transaction, &tracked))
+            .chain(asset_transactions.iter().map(|(tx, addresses)| (tx, addresses)))
+            .chain(external_transactions.iter().map(|(tx, addresses)| (tx, addresses)))
+            .chain(private_transactions.iter().map(|tx| (tx, &no_addresses)))
+            .chain(liability_transaction.iter().map(|tx| (tx, &no_addresses)));
+        for (broadcast_transaction, addresses) in broadcast {
+            let id = broadcast_transaction.id().to_string();
+            let function = function_name(broadcast_transaction);
+            st.pending.push(PendingTransaction {
+                id: id.clone(),
+                function: function.clone(),
+                addresses: addresses.clone(),
+                broadcast: Utc::now().timestamp(),
+            });
+            emit(&mut st, WebhookEvent::AttestationBroadcast { transaction: id, function }).await;
+        }
+        if let Err(e) = crate::storage::save_pending(&st.pending, &st.pending_file).await {
+            error!("Failed to save pending transactions: {}", e);
+        }
+        for error in failures {
+            emit(&mut st, WebhookEvent::ProvingFailed { run_id: run_id.to_string(), error }).await;
+        }
+        saved
     }
+    .instrument(info_span!("storage"))
+    .await?;
 
     Ok(())
 }
 
 
+/// The name of the root function of an execution, e.g. `record_balances8`.
+fn function_name(transaction: &Transaction<CurrentNetwork>) -> String {
+    transaction.execution()
+        .and_then(|execution| execution.transitions().last())
+        .map(|transition| format!("{}/{}", transition.program_id(), transition.function_name()))
+        .unwrap_or_default()
+}
+
+/// The addresses among the public inputs of the attestation's root function, which are the ones
+/// it covers.
+fn covered_addresses(transaction: &Transaction<CurrentNetwork>) -> Vec<String> {
+    fn collect(plaintext: &Plaintext<CurrentNetwork>, addresses: &mut Vec<String>) {
+        match plaintext {
+            Plaintext::Literal(Literal::Address(address), _) => addresses.push(address.to_string()),
+            Plaintext::Array(elements, _) => elements.iter().for_each(|element| collect(element, addresses)),
+            _ => {}
+        }
+    }
+    let mut addresses = Vec::new();
+    let inputs = transaction.execution()
+        .and_then(|execution| execution.transitions().last())
+        .map(|transition| transition.inputs())
+        .unwrap_or_default();
+    for input in inputs {
+        if let Input::Public(_, Some(plaintext)) = input {
+            collect(plaintext, &mut addresses);
+        }
+    }
+    addresses
+}
+
+/// Signs a report for each accepted attestation that was tracked before reports were kept, whose
+/// report failed to sign, or whose stored report no longer verifies, and records how the network
+/// resolved the ones tracked before resolutions were kept. Attestations that cannot be resolved are
+/// retried on the next start.
+async fn backfill_reports(app_state: &Arc<RwLock<AppState>>) {
+    let (missing, endpoint, private_key) = {
+        let st = app_state.read().await;
+        let missing = st.transactions.iter()
+            .filter(|tracked| !st.pending.iter().any(|pending| pending.id == tracked.id))
+            .filter(|tracked| tracked.resolution != Some(Resolution::Rejected))
+            .filter(|tracked| tracked.resolution.is_none() || st.reports.get(&tracked.id).is_none_or(|signed| signed.verify().is_err()))
+            .map(|tracked| tracked.id.clone())
+            .collect::<Vec<_>>();
+        (missing, st.endpoint.clone(), st.private_key.clone())
+    };
+    if missing.is_empty() {
+        return;
+    }
+    let resolved = spawn_blocking(move || {
+        missing.into_iter()
+            .filter_map(|id| {
+                let details = get_attestation_details(&id, &private_key, &endpoint, NETWORK_NAME)
+                    .map_err(|e| warn!("Failed to read attestation {} for its report: {}", id, e))
+                    .ok()?;
+                if !details.transaction.is_accepted() {
+                    return Some((id, Resolution::Rejected, None));
+                }
+                let transaction = details.transaction.transaction();
+                let report = AttestationReport::new(&id, &function_name(transaction), covered_addresses(transaction), &details, NETWORK_NAME)
+                    .and_then(|report| report.sign(&private_key))
+                    .map_err(|e| warn!("Failed to sign the report for {}: {}", id, e))
+                    .ok();
+                Some((id, Resolution::Accepted, report))
+            })
+            .collect::<Vec<_>>()
+    }).await.unwrap_or_default();
+    if resolved.is_empty() {
+        return;
+    }
+    let mut st = app_state.write().await;
+    let mut signed = 0;
+    for (id, resolution, report) in resolved {
+        if let Some(tracked) = st.transactions.iter_mut().find(|tracked| tracked.id == id) {
+            tracked.resolution = Some(resolution);
+        }
+        // Keep a stored report that still verifies rather than re-signing it with a new timestamp.
+        if let Some(report) = report.filter(|_| st.reports.get(&id).is_none_or(|signed| signed.verify().is_err())) {
+            st.reports.insert(id, report);
+            signed += 1;
+        }
+    }
+    info!("Signed {} reports for earlier attestations", signed);
+    if let Err(e) = crate::storage::save_transactions(&st.transactions, &st.transactions_file).await {
+        error!("Failed to save transactions: {}", e);
+    }
+    if let Err(e) = crate::storage::save_reports(&st.reports, &st.reports_file).await {
+        error!("Failed to save reports: {}", e);
+    }
+}
+
+/// Stores an evidence bundle for each tracked attestation that has none, so the public site can serve
+/// it without calling the endpoint. Attestations that cannot be exported are retried on the next start.
+async fn backfill_evidence(app_state: &Arc<RwLock<AppState>>) {
+    let (missing, endpoint, private_key, dir) = {
+        let st = app_state.read().await;
+        let missing = st.transactions.iter()
+            .filter(|tracked| tracked.resolution == Some(Resolution::Accepted) && !st.evidence.contains(&tracked.id))
+            .map(|tracked| tracked.id.clone())
+            .collect::<Vec<_>>();
+        (missing, st.endpoint.clone(), st.private_key.clone(), st.evidence_dir.clone())
+    };
+    for id in missing {
+        let (txid, endpoint, private_key) = (id.clone(), endpoint.clone(), private_key.clone());
+        let bundle = match spawn_blocking(move || export_bundle(&txid, &private_key, &endpoint, NETWORK_NAME)).await {
+            Ok(Ok(bundle)) => bundle,
+            Ok(Err(e)) => {
+                warn!("Failed to export the evidence bundle for {}: {}", id, e);
+                continue;
+            }
+            Err(e) => {
+                warn!("Export task for {} failed: {:?}", id, e);
+                continue;
+            }
+        };
+        match crate::storage::save_bundle(&bundle, &dir).await {
+            Ok(()) => {
+                app_state.write().await.evidence.insert(id);
+            }
+            Err(e) => error!("Failed to store the evidence bundle for {}: {}", id, e),
+        }
+    }
+}
+
+/// Checks the pending transactions until each is confirmed, notifying the webhooks of the outcome,
+/// signing a report for each accepted attestation and evaluating the alert rules. A transaction that is not confirmed within an hour counts as rejected.
+/// On startup, it first signs the missing reports and stores the missing evidence bundles of earlier attestations.
+pub fn spawn_confirmation_task(app_state: Arc<RwLock<AppState>>) {
+    tokio::spawn(async move {
+        backfill_reports(&app_state).await;
+        backfill_evidence(&app_state).await;
+        let mut ticker = interval(CONFIRMATION_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let (pending, endpoint, private_key, addresses, rules, portfolios, price_feed, currency, transactions, hides_totals) = {
+                let st = app_state.read().await;
+                // Solvency mode records no valuation, so no price is fetched either.
+                let price_feed = st.price_feed.clone().filter(|_| !st.hides_totals());
+                (st.pending.clone(), st.endpoint.clone(), st.private_key.clone(), st.addresses.clone(), st.alert_rules.clone(),
+                    st.portfolios.clone(), price_feed, st.currency.clone(), st.transactions.clone(), st.hides_totals())
+            };
+            // Staleness does not wait on an attestation resolving.
+            evaluate(&mut *app_state.write().await, None).await;
+            for transaction in pending {
+                let id = transaction.id.clone();
+                let url = endpoint.clone();
+                let private_key = private_key.clone();
+                let addresses = addresses.clone();
+                let rules = rules.clone();
+                let tags = portfolios.clone();
+                let function = transaction.function.clone();
+                let covered = transaction.addresses.clone();
+                let kind = transactions.iter().find(|tracked| tracked.id == transaction.id).map(|tracked| tracked.kind);
+                let reserves = kind == Some(TransactionKind::Reserves);
+                // A token asset of a portfolio is attested over just its addresses, and its totals
+                // are compared with that portfolio's.
+                let portfolio = portfolios.iter()
+                    .filter(|_| kind == Some(TransactionKind::Token) && covered != addresses)
+                    .find(|portfolio| !portfolio.assets.is_empty() && portfolio.addresses == covered)
+                    .map(|portfolio| portfolio.name.clone());
+                let price_feed = price_feed.clone();
+                let currency = currency.clone();
+                let confirmed = spawn_blocking(move || {
+                    let confirmed = get_confirmed_transaction::<CurrentNetwork>(&id, &url, NETWORK_NAME)?;
+                    let height = get_block_height(&id, &url, NETWORK_NAME)?.parse::<u32>()?;
+                    if !confirmed.is_accepted() {
+                        return Ok((false, height, None, None, Vec::new(), None, None));
+                    }
+                    // The liabilities commitment has no reserve total to report or check.
+                    if function.ends_with("/record_liabilities") {
+                        return Ok((true, height, Some(Observation::default()), None, Vec::new(), None, None));
+                    }
+                    let details = get_attestation_details(&id, &private_key, &url, NETWORK_NAME)
+                        .map_err(|e| warn!("Failed to read attestation {}: {}", id, e))
+                        .ok();
+                    let report = details.as_ref().and_then(|details| {
+                        AttestationReport::new(&id, &function, covered, details, NETWORK_NAME)
+                            .and_then(|report| report.sign(&private_key))
+                            .map_err(|e| warn!("Failed to sign the report for {}: {}", id, e))
+                            .ok()
+                    });
+                    // Record the price of each valued asset at the block timestamp.
+                    let prices = match (&details, &price_feed) {
+                        (Some(details), Some(feed)) => valued_totals(&details.result)
+                            .into_iter()
+                            .filter_map(|(asset, ..)| match feed.price_at(&asset, details.unix_timestamp) {
+                                Ok(price_micros) => Some(RecordedPrice { asset, timestamp: details.unix_timestamp, price_micros, currency: currency.clone() }),
+                                Err(e) => {
+                                    warn!("Failed to record the {} price for {}: {}", asset, id, e);
+                                    None
+                                }
+                            })
+                            .collect(),
+                        _ => Vec::new(),
+                    };
+                    let observation = Observation {
+                        reserves,
+                        portfolio,
+                        ..observe(details.as_ref().map(|details| &details.result), &addresses, &rules, &tags, &url, NETWORK_NAME)
+                    };
+                    // Store the evidence now, so the public site never has to call the endpoint for it.
+                    let bundle = details.as_ref().and_then(|_| {
+                        export_bundle(&id, &private_key, &url, NETWORK_NAME)
+                            .map_err(|e| warn!("Failed to export the evidence bundle for {}: {}", id, e))
+                            .ok()
+                    });
+                    // Verify the proofs now rather than the first time the attestation is shown.
+                    let verification = details.as_ref().and_then(|details| {
+                        check_attestation(details.transaction.transaction(), &private_key, &url)
+                            .map_err(|e| warn!("Could not verify attestation {} yet: {}", id, e))
+                            .ok()
+                    });
+                    Ok::<_, snarkvm::prelude::Error>((true, height, Some(observation), report, prices, bundle, verification))
+                }).await;
+
+                let (event, observation, report, prices, bundle, verification) = match confirmed {
+                    Ok(Ok((true, height, observation, report, prices, bundle, verification))) => {
+                        (WebhookEvent::AttestationAccepted { transaction: transaction.id.clone(), height }, observation, report, prices, bundle, verification)
+                    }
+                    Ok(Ok((false, height, ..))) => (WebhookEvent::AttestationRejected {
+                        transaction: transaction.id.clone(),
+                        reason: format!("rejected in block {}", height),
+                    }, None, None, Vec::new(), None, None),
+                    _ if Utc::now().timestamp() - transaction.broadcast > PENDING_TIMEOUT.as_secs() as i64 => {
+                        (WebhookEvent::AttestationRejected {
+                            transaction: transaction.id.clone(),
+                            reason: format!("not confirmed within {} minutes", PENDING_TIMEOUT.as_secs() / 60),
+                        }, None, None, Vec::new(), None, None)
+                    }
+                    _ => continue,
+                };
+                info!("Attestation {} resolved: {}", transaction.id, event.name());
+                let resolution = match event {
+                    WebhookEvent::AttestationAccepted { .. } => Resolution::Accepted,
+                    _ => Resolution::Rejected,
+                };
+                let mut st = app_state.write().await;
+                if let Some(tracked) = st.transactions.iter_mut().find(|tracked| tracked.id == transaction.id) {
+                    tracked.resolution = Some(resolution);
+                    if let Err(e) = crate::storage::save_transactions(&st.transactions, &st.transactions_file).await {
+                        error!("Failed to save transactions: {}", e);
+                    }
+                }
+                st.pending.retain(|pending| pending.id != transaction.id);
+                if let Err(e) = crate::storage::save_pending(&st.pending, &st.pending_file).await {
+                    error!("Failed to save pending transactions: {}", e);
+                }
+                if let Some(report) = report {
+                    st.reports.insert(transaction.id.clone(), report);
+                    if let Err(e) = crate::storage::save_reports(&st.reports, &st.reports_file).await {
+                        error!("Failed to save reports: {}", e);
+                    }
+                }
+                if let Some(bundle) = bundle {
+                    match crate::storage::save_bundle(&bundle, &st.evidence_dir).await {
+                        Ok(()) => {
+                            st.evidence.insert(transaction.id.clone());
+                        }
+                        Err(e) => error!("Failed to store the evidence bundle for {}: {}", transaction.id, e),
+                    }
+                }
+                if let Some(verification) = verification {
+                    st.verifications.insert(transaction.id.clone(), verification);
+                    if let Err(e) = crate::storage::save_verifications(&st.verifications, &st.verifications_file).await {
+                        error!("Failed to save verifications: {}", e);
+                    }
+                }
+                if !prices.is_empty() {
+                    st.prices.insert(transaction.id.clone(), prices);
+                    if let Err(e) = crate::storage::save_prices(&st.prices, &st.prices_file).await {
+                        error!("Failed to save prices: {}", e);
+                    }
+                }
+                // Cache the split by portfolio for the history charts. The balances are read on
+                // acceptance, so they can differ slightly from the ones the attestation proved.
+                if let Some(balances) = observation.as_ref().and_then(|observation| observation.balances.as_ref()) {
+                    if !portfolios.is_empty() && !hides_totals {
+                        st.breakdowns.insert(transaction.id.clone(), tag_totals(balances, &portfolios));
+                        if let Err(e) = crate::storage::save_breakdowns(&st.breakdowns, &st.breakdowns_file).await {
+                            error!("Failed to save breakdowns: {}", e);
+                        }
+                    }
+                }
+                if observation.as_ref().is_some_and(|observation| observation.reserves) {
+                    METRICS.mark_accepted(Utc::now().timestamp());
+                }
+                for (asset, total) in observation.iter().flat_map(|observation| &observation.totals) {
+                    METRICS.set_last_total(asset, *total);
+                }
+                emit(&mut st, event).await;
+                evaluate(&mut st, observation).await;
+            }
+        }
+    });
+}
+
+/// Parses the tracked addresses for a program input.
+fn parse_addresses(addresses: &[String]) -> Result<Vec<Address<CurrentNetwork>>, String> {
+    addresses.iter()
+        .map(|addr_str| Address::<CurrentNetwork>::from_str(addr_str)
+            .map_err(|e| format!("Failed to parse address '{}': {}", addr_str, e))
+        )
+        .collect()
+}
+
+/// Packs the addresses into the array input of the variant matching their count.
+fn addresses_input(addresses: Vec<Address<CurrentNetwork>>) -> Result<(usize, Value<CurrentNetwork>), String> {
+    let size = addresses.len();
+    let literals = addresses.into_iter()
+        .map(|addr| Plaintext::from(Literal::Address(addr)))
+        .collect::<Vec<_>>();
+    let array = match size {
+        1 | 2 | 4 | 8 | 16 | 32 => Plaintext::Array(literals, Default::default()),
+        // The 64 variant takes two rows of 32 addresses.
+        64 => Plaintext::Array(
+            literals.chunks(32).map(|row| Plaintext::Array(row.to_vec(), Default::default())).collect(),
+            Default::default()
+        ),
+        _ => return Err(format!("Cannot attest {} addresses; track 1, 2, 4, 8, 16, 32 or 64 addresses", size)),
+    };
+    Ok((size, Value::Plaintext(array)))
+}
+
 fn prove_for_address(
     addresses: Vec<Address<CurrentNetwork>>,
     private_key: PrivateKey<CurrentNetwork>,
     endpoint: &str,
+    threshold: Option<u64>,
+    include_stake: bool,
+    priority_fee: u64,
 ) -> Result<Transaction<CurrentNetwork>, String> {
 
     let vm = get_or_init_vm()?;
-    let rng = &mut rand::rngs::OsRng;
 
     let first = Value::Plaintext(Plaintext::from(Literal::Address(Address::try_from(&private_key).unwrap())));
-    let second = Value::Plaintext(Plaintext::Array(
-        addresses.into_iter().map(|addr| Plaintext::from(Literal::Address(addr))).collect(),
-        Default::default()
-    ));
-    let inputs = vec![first, second];
+    let (size, second) = addresses_input(addresses)?;
+    let mut inputs = vec![first, second];
+    // Solvency mode only records whether the reserves cover the threshold.
+    let (program_id, function_name) = match threshold {
+        Some(threshold) => {
+            inputs.push(Value::Plaintext(Plaintext::from(Literal::U64(U64::new(threshold)))));
+            (SOLVENCY_PROGRAM_ID, format!("prove_solvency{}", size))
+        }
+        // The stake variants also break out bonded and unbonding credits, but stop at 32 addresses.
+        None if include_stake && size == 64 => return Err("Cannot include stake for 64 addresses; track at most 32".to_string()),
+        None if include_stake => (PROGRAM_ID, format!("record_stake_balances{}", size)),
+        // Plain balances stay on the deployed first program, so its attestations keep one history.
+        None => (LEGACY_PROGRAM_ID, format!("record_balances{}", size)),
+    };
+    let transaction = execute_and_broadcast(&vm, &private_key, (program_id, function_name.as_str()), &inputs, endpoint, priority_fee)?;
+    info!("Broadcasted transaction: {}", transaction.id());
+
+    Ok(transaction)
+}
+
+fn prove_token_balances(
+    addresses: Vec<Address<CurrentNetwork>>,
+    token_id: &str,
+    private_key: PrivateKey<CurrentNetwork>,
+    endpoint: &str,
+    priority_fee: u64,
+) -> Result<Transaction<CurrentNetwork>, String> {
+
+    let token_id = Field::<CurrentNetwork>::from_str(token_id)
+        .map_err(|e| format!("Failed to parse token ID '{}': {}", token_id, e))?;
+
+    let mut vm = get_or_init_vm()?;
+    load_token_program(&mut vm, || token_registry(endpoint))?;
+
+    let first = Value::Plaintext(Plaintext::from(Literal::Address(Address::try_from(&private_key).unwrap())));
+    let (size, second) = addresses_input(addresses)?;
+    let third = Value::Plaintext(Plaintext::from(Literal::Field(token_id)));
+    let inputs = [first, second, third];
+    let function_name = format!("record_token_balances{}", size);
+    let transaction = execute_and_broadcast(&vm, &private_key, (TOKEN_PROGRAM_ID, function_name.as_str()), &inputs, endpoint, priority_fee)?;
+    info!("Broadcasted token balances transaction: {}", transaction.id());
+
+    Ok(transaction)
+}
+
+/// Commits the total of each source in one `record_external_balances` attestation, padding the
+/// unused entries with zeros.
+fn prove_external_balances(
+    totals: &[(&dyn BalanceSource, u128)],
+    private_key: PrivateKey<CurrentNetwork>,
+    endpoint: &str,
+    priority_fee: u64,
+) -> Result<Transaction<CurrentNetwork>, String> {
+    if totals.len() > MAX_EXTERNAL_SOURCES {
+        return Err(format!("Cannot commit {} sources in one attestation; configure at most {}", totals.len(), MAX_EXTERNAL_SOURCES));
+    }
+
+    let vm = get_or_init_vm()?;
+
+    let entry = |asset: Field<CurrentNetwork>, addresses: Field<CurrentNetwork>, total: u128| {
+        Plaintext::<CurrentNetwork>::from_str(&format!("{{asset: {}, addresses: {}, total: {}u128}}", asset, addresses, total))
+            .map_err(|e| format!("Failed to build external total: {}", e))
+    };
+    let mut entries = totals.iter()
+        .map(|(source, total)| entry(asset_field(source.asset())?, hash_addresses(source.addresses())?, *total))
+        .collect::<Result<Vec<_>, String>>()?;
+    while entries.len() < MAX_EXTERNAL_SOURCES {
+        entries.push(entry(Field::zero(), Field::zero(), 0)?);
+    }
+    let inputs = [Value::Plaintext(Plaintext::Array(entries, Default::default()))];
+    let transaction = execute_and_broadcast(&vm, &private_key, (PROGRAM_ID, "record_external_balances"), &inputs, endpoint, priority_fee)?;
+    info!("Broadcasted external balances transaction: {}", transaction.id());
+
+    Ok(transaction)
+}
+
+/// Proves ownership of the records in batches matching the `record_private_balances*` variants.
+fn prove_private_records(
+    records: Vec<Record<CurrentNetwork, Plaintext<CurrentNetwork>>>,
+    private_key: PrivateKey<CurrentNetwork>,
+    endpoint: &str,
+    priority_fee: u64,
+) -> Result<Vec<Transaction<CurrentNetwork>>, String> {
+    let mut transactions = Vec::new();
+    let mut remaining = records.as_slice();
+    while !remaining.is_empty() {
+        let size = [8, 4, 2, 1].into_iter().find(|size| *size <= remaining.len()).unwrap();
+        let (batch, rest) = remaining.split_at(size);
+        remaining = rest;
+
+        let vm = get_or_init_vm()?;
+
+        // The program reads each amount from its record, so only their sum is made public.
+        let inputs = batch.iter().map(|record| Value::Record(record.clone())).collect::<Vec<_>>();
+        let function_name = format!("record_private_balances{}", size);
+        let transaction = execute_and_broadcast(&vm, &private_key, (PROGRAM_ID, function_name.as_str()), &inputs, endpoint, priority_fee)?;
+        info!("Broadcasted private records transaction: {}", transaction.id());
+
+        transactions.push(transaction);
+    }
+
+    Ok(transactions)
+}
+
+fn prove_liabilities(
+    root: Field<CurrentNetwork>,
+    total: u64,
+    private_key: PrivateKey<CurrentNetwork>,
+    endpoint: &str,
+    priority_fee: u64,
+) -> Result<Transaction<CurrentNetwork>, String> {
+
+    let vm = get_or_init_vm()?;
+
+    let inputs = [
+        Value::Plaintext(Plaintext::from(Literal::Field(root))),
+        Value::Plaintext(Plaintext::from(Literal::U64(U64::new(total)))),
+    ];
+    let transaction = execute_and_broadcast(&vm, &private_key, (PROGRAM_ID, "record_liabilities"), &inputs, endpoint, priority_fee)?;
+    info!("Broadcasted liabilities transaction: {}", transaction.id());
+
+    Ok(transaction)
+}
+
+/// Executes a function in the VM and broadcasts the transaction, timing both stages.
+/// The fee is paid publicly, with the priority fee in microcredits on top of the base fee.
+fn execute_and_broadcast(
+    vm: &VM<CurrentNetwork, ConsensusMemory<CurrentNetwork>>,
+    private_key: &PrivateKey<CurrentNetwork>,
+    (program_id, function_name): (&str, &str),
+    inputs: &[Value<CurrentNetwork>],
+    endpoint: &str,
+    priority_fee: u64,
+) -> Result<Transaction<CurrentNetwork>, String> {
+    let rng = &mut rand::rngs::OsRng;
     let query = Some(Query::REST(endpoint.to_string()));
 
-    let transaction = vm.execute(
-        &private_key,
-        ("proof_of_reserves_v0_1_0.aleo", "record_balances2"),
-        inputs.iter(),
-        None,
-        0u64,
-        query,
-        rng
-    ).map_err(|e| format!("Failed to execute VM: {}", e))?;
+    let start = Instant::now();
+    let transaction = info_span!("execute", program = program_id, function = function_name).in_scope(|| {
+        vm.execute(
+            private_key,
+            (program_id, function_name),
+            inputs.iter(),
+            None,
+            priority_fee,
+            query,
+            rng
+        ).map_err(|e| {
+            error!("Failed to execute VM: {}", e);
+            format!("Failed to execute VM: {}", e)
+        })
+    })?;
+    METRICS.observe_proving(function_name, start.elapsed());
 
     // Broadcast the transaction to the endpoint.
-    broadcast_transaction(&transaction, endpoint, "testnet")
-        .map_err(|e| format!("Failed to broadcast transaction: {}", e))?;
-    println!("Broadcasted transaction: {:?}", transaction.id());
+    let start = Instant::now();
+    info_span!("broadcast", function = function_name, transaction = %transaction.id()).in_scope(|| {
+        broadcast_transaction(&transaction, endpoint, "testnet").map_err(|e| {
+            error!("Failed to broadcast transaction: {}", e);
+            format!("Failed to broadcast transaction: {}", e)
+        })
+    })?;
+    METRICS.observe_broadcast(function_name, start.elapsed());
 
     Ok(transaction)
 }
 
-
 fn get_or_init_vm() -> Result<std::sync::MutexGuard<'static, VM<CurrentNetwork, ConsensusMemory<CurrentNetwork>>>, String> {
     // Initialize VM_GLOBAL if not already done
     VM_GLOBAL.get_or_try_init(|| {
+        let _span = info_span!("vm_init").entered();
         let vm = VM::from(ConsensusStore::<CurrentNetwork, ConsensusMemory<CurrentNetwork>>::open(None)
             .map_err(|e| format!("Failed to open consensus store: {}", e))?)
             .map_err(|e| format!("Failed to create VM: {}", e))?;
 
-        let program_str = include_str!("../../proof_of_reserves/build/main.aleo");
-        let program = Program::from_str(program_str)
-            .map_err(|e| format!("Failed to parse program: {}", e))?;
+        let program_strs = [
+            include_str!("../../proof_of_reserves/build/main.aleo"),
+            include_str!("../../proof_of_reserves_v0_2_0/build/main.aleo"),
+            include_str!("../../proof_of_solvency/build/main.aleo"),
+        ];
+        for program_str in program_strs {
+            let program = Program::from_str(program_str)
+                .map_err(|e| format!("Failed to parse program: {}", e))?;
 
-        {
             let deployment = vm.process().read()
                 .deploy::<CurrentAleo, _>(&program, &mut rand::rngs::OsRng)
                 .map_err(|e| format!("Failed to deploy program: {}", e))?;
@@ -157,3 +873,69 @@ fn get_or_init_vm() -> Result<std::sync::MutexGuard<'static, VM<CurrentNetwork,
     Ok(VM_GLOBAL.get().unwrap().lock().unwrap())
 }
 
+/// Fetches the source of `token_registry.aleo` from the endpoint.
+pub fn token_registry(endpoint: &str) -> Result<String, String> {
+    get_program("token_registry.aleo", endpoint, NETWORK_NAME)
+        .map_err(|e| format!("Failed to fetch token registry: {}", e))
+}
+
+/// Deploys the token reserves program on first use. Its `token_registry.aleo` import is already
+/// deployed on the network, so its source is only asked for when the program is not loaded yet.
+fn load_token_program(
+    vm: &mut VM<CurrentNetwork, ConsensusMemory<CurrentNetwork>>,
+    registry: impl FnOnce() -> Result<String, String>,
+) -> Result<(), String> {
+    let program_id = ProgramID::from_str(TOKEN_PROGRAM_ID).map_err(|e| e.to_string())?;
+    if vm.process().read().contains_program(&program_id) {
+        return Ok(());
+    }
+
+    let registry_str = registry()?;
+    let registry = Program::from_str(&registry_str)
+        .map_err(|e| format!("Failed to parse token registry: {}", e))?;
+    vm.process().write()
+        .add_program(&registry)
+        .map_err(|e| format!("Failed to load token registry: {}", e))?;
+
+    let program = Program::from_str(include_str!("../../proof_of_token_reserves/build/main.aleo"))
+        .map_err(|e| format!("Failed to parse program: {}", e))?;
+    let deployment = vm.process().read()
+        .deploy::<CurrentAleo, _>(&program, &mut rand::rngs::OsRng)
+        .map_err(|e| format!("Failed to deploy program: {}", e))?;
+    vm.process().write()
+        .load_deployment(&deployment)
+        .map_err(|e| format!("Failed to load deployment: {}", e))?;
+
+    Ok(())
+}
+
+
+/// Verifies the execution and fee proofs of a transaction against the verifying keys of the
+/// in-process VM, so the result does not rest on trusting the endpoint. The global state root is
+/// not checked, since the in-process ledger does not follow the network.
+pub fn verify_proofs(
+    transaction: &Transaction<CurrentNetwork>,
+    registry: impl FnOnce() -> Result<String, String>,
+) -> Result<(), String> {
+    let execution = transaction.execution()
+        .ok_or_else(|| format!("Transaction {} is not an execution", transaction.id()))?;
+    let program_id = execution.transitions().last()
+        .map(|transition| transition.program_id().to_string())
+        .ok_or_else(|| format!("Transaction {} has no transitions", transaction.id()))?;
+
+    let mut vm = get_or_init_vm()?;
+    if program_id == TOKEN_PROGRAM_ID {
+        load_token_program(&mut vm, registry)?;
+    }
+    let process = vm.process();
+    let process = process.read();
+    process.verify_execution(execution)
+        .map_err(|e| format!("Invalid execution proof: {}", e))?;
+    if let Some(fee) = transaction.fee_transition() {
+        let execution_id = execution.to_execution_id()
+            .map_err(|e| format!("Failed to compute execution ID: {}", e))?;
+        process.verify_fee(&fee, execution_id)
+            .map_err(|e| format!("Invalid fee proof: {}", e))?;
+    }
+    Ok(())
+}
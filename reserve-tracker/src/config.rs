@@ -0,0 +1,471 @@
+use clap::{parser::ValueSource, ArgMatches};
+use serde::{Deserialize, Serialize};
+use snarkvm::prelude::{Address, Field, PrivateKey, ViewKey};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+
+use crate::alerts::AlertRule;
+use crate::background::{BackgroundTaskMsg, MAX_EXTERNAL_SOURCES};
+use crate::liabilities::LiabilityTree;
+use crate::prices::PricePoint;
+use crate::sources::SourceConfig;
+use crate::storage::{load_bundle_ids, load_customer_balances, load_sources, load_static_prices};
+use crate::webhooks::{emit, WebhookConfig, WebhookEvent};
+use crate::state::{AppState, Mode};
+use crate::{Cli, CurrentNetwork, NETWORK_NAME};
+
+/// The settings read from the TOML config file. Every setting is optional, and flags and
+/// environment variables take precedence over it.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    pub port: Option<u16>,
+    pub public_port: Option<u16>,
+    pub network: Option<String>,
+    pub endpoints: Option<Vec<String>>,
+    pub light_verification: Option<bool>,
+    pub inclusion_quorum: Option<usize>,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub schedule: ScheduleConfig,
+    #[serde(default)]
+    pub fees: FeeConfig,
+    #[serde(default)]
+    pub attestation: AttestationConfig,
+    #[serde(default)]
+    pub prices: PriceConfig,
+    #[serde(default)]
+    pub portfolios: Vec<Portfolio>,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    #[serde(default)]
+    pub alerts: Vec<AlertRule>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StorageConfig {
+    pub addresses: Option<String>,
+    pub assets: Option<String>,
+    pub transactions: Option<String>,
+    pub liabilities: Option<String>,
+    pub liability_transactions: Option<String>,
+    pub records: Option<String>,
+    pub prices: Option<String>,
+    pub sources: Option<String>,
+    pub customer_balances: Option<String>,
+    pub static_prices: Option<String>,
+    pub outbox: Option<String>,
+    pub pending: Option<String>,
+    pub alerts: Option<String>,
+    pub reports: Option<String>,
+    pub verifications: Option<String>,
+    pub breakdowns: Option<String>,
+    pub evidence: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+    /// Seconds between attestations.
+    pub cadence: Option<u64>,
+    pub max_attestation_age: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeeConfig {
+    /// Priority fee paid on each attestation, in microcredits.
+    pub priority_fee: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AttestationConfig {
+    pub mode: Option<Mode>,
+    /// Threshold for solvency mode, in microcredits.
+    pub solvency_threshold: Option<u64>,
+    pub include_stake: Option<bool>,
+    /// View keys to scan for private records.
+    pub view_keys: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PriceConfig {
+    /// URL of an HTTP JSON price feed.
+    pub url: Option<String>,
+    /// JSON pointer to the price in the feed's response.
+    pub pointer: Option<String>,
+    pub currency: Option<String>,
+}
+
+/// The files the settings point to, read and checked by `validate`.
+pub struct Inputs {
+    pub sources: Vec<SourceConfig>,
+    pub static_prices: Option<HashMap<String, Vec<PricePoint>>>,
+    /// The transaction IDs of the stored evidence bundles.
+    pub evidence: HashSet<String>,
+    pub liabilities: Option<LiabilityTree>,
+}
+
+/// A named group of addresses, added to the tracked addresses, and of token assets attested over
+/// just those addresses.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Portfolio {
+    pub name: String,
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    #[serde(default)]
+    pub assets: Vec<String>,
+}
+
+pub fn load_config(file: &str) -> Result<FileConfig, String> {
+    let contents = std::fs::read_to_string(file)
+        .map_err(|e| format!("Failed to read config file {}: {}", file, e))?;
+    toml::from_str(&contents).map_err(|e| format!("Failed to parse config file {}: {}", file, e))
+}
+
+/// Whether a flag was given on the command line or through its environment variable.
+fn explicit(matches: &ArgMatches, id: &str) -> bool {
+    matches!(matches.value_source(id), Some(ValueSource::CommandLine | ValueSource::EnvVariable))
+}
+
+/// Fills in the flags that were not set explicitly from the config file.
+pub fn apply_config(cli: &mut Cli, matches: &ArgMatches, config: &FileConfig) {
+    macro_rules! merge {
+        ($field:ident, $value:expr) => {
+            if let Some(value) = $value.clone() {
+                if !explicit(matches, stringify!($field)) {
+                    cli.$field = value;
+                }
+            }
+        };
+    }
+    merge!(port, config.port);
+    merge!(public_port, config.public_port.map(Some));
+    merge!(network, config.network);
+    merge!(endpoints, config.endpoints);
+    merge!(light_verification, config.light_verification);
+    merge!(inclusion_quorum, config.inclusion_quorum.map(Some));
+    merge!(file, config.storage.addresses);
+    merge!(assets_file, config.storage.assets);
+    merge!(transactions_file, config.storage.transactions);
+    merge!(liabilities_file, config.storage.liabilities);
+    merge!(liability_transactions_file, config.storage.liability_transactions);
+    merge!(records_file, config.storage.records);
+    merge!(prices_file, config.storage.prices);
+    merge!(outbox_file, config.storage.outbox);
+    merge!(pending_file, config.storage.pending);
+    merge!(alerts_file, config.storage.alerts);
+    merge!(reports_file, config.storage.reports);
+    merge!(verifications_file, config.storage.verifications);
+    merge!(breakdowns_file, config.storage.breakdowns);
+    merge!(evidence_dir, config.storage.evidence);
+    merge!(sources_file, config.storage.sources.as_ref().map(|file| Some(file.clone())));
+    merge!(customer_balances, config.storage.customer_balances.as_ref().map(|file| Some(file.clone())));
+    merge!(static_prices, config.storage.static_prices.as_ref().map(|file| Some(file.clone())));
+    merge!(cadence, config.schedule.cadence);
+    merge!(max_attestation_age, config.schedule.max_attestation_age.map(Some));
+    merge!(priority_fee, config.fees.priority_fee);
+    merge!(mode, config.attestation.mode);
+    merge!(solvency_threshold, config.attestation.solvency_threshold.map(Some));
+    merge!(include_stake, config.attestation.include_stake);
+    merge!(view_keys, config.attestation.view_keys);
+    merge!(price_url, config.prices.url.as_ref().map(|url| Some(url.clone())));
+    merge!(price_pointer, config.prices.pointer);
+    merge!(currency, config.prices.currency);
+}
+
+/// Checks the merged settings and reads the files they point to, returning every problem found
+/// rather than stopping at the first.
+pub async fn validate(cli: &Cli, config: &FileConfig) -> Result<Inputs, Vec<String>> {
+    let mut errors = Vec::new();
+
+    if cli.port == 0 {
+        errors.push("port must be between 1 and 65535".to_string());
+    }
+    if cli.public_port == Some(0) {
+        errors.push("public_port must be between 1 and 65535".to_string());
+    } else if cli.public_port == Some(cli.port) {
+        errors.push("public_port must differ from the admin port".to_string());
+    }
+    if cli.network != NETWORK_NAME {
+        errors.push(format!("network '{}' is not supported; this build attests on '{}'", cli.network, NETWORK_NAME));
+    }
+    if cli.endpoints.is_empty() {
+        errors.push("at least one endpoint is required".to_string());
+    }
+    if let Some(quorum) = cli.inclusion_quorum.filter(|quorum| *quorum == 0 || *quorum > cli.endpoints.len()) {
+        errors.push(format!("inclusion_quorum {} must be between 1 and the number of endpoints, {}", quorum, cli.endpoints.len()));
+    }
+    for endpoint in &cli.endpoints {
+        if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+            errors.push(format!("endpoint '{}' must be an http:// or https:// URL", endpoint));
+        }
+    }
+    if cli.cadence == 0 {
+        errors.push("cadence must be at least 1 second".to_string());
+    }
+    if cli.max_attestation_age == Some(0) {
+        errors.push("max_attestation_age must be at least 1 second".to_string());
+    }
+    if PrivateKey::<CurrentNetwork>::from_str(&cli.private_key).is_err() {
+        errors.push("private key is not a valid Aleo private key".to_string());
+    }
+    for view_key in &cli.view_keys {
+        if ViewKey::<CurrentNetwork>::from_str(view_key).is_err() {
+            errors.push("a view key is not a valid Aleo view key".to_string());
+        }
+    }
+    if cli.mode == Mode::Solvency && cli.solvency_threshold.is_none() && cli.customer_balances.is_none() {
+        errors.push("solvency mode requires a solvency threshold or customer balances".to_string());
+    }
+    // A flag can name one and the config file the other, so clap does not catch this.
+    if cli.price_url.is_some() && cli.static_prices.is_some() {
+        errors.push("price_url and static_prices are mutually exclusive".to_string());
+    }
+
+    for (name, file) in storage_paths(cli) {
+        let Some(file) = file else { continue };
+        match Path::new(file).parent() {
+            _ if file.is_empty() => errors.push(format!("storage path '{}' is empty", name)),
+            Some(dir) if !dir.as_os_str().is_empty() && !dir.is_dir() => {
+                errors.push(format!("storage path '{}' is in a missing directory: {}", name, dir.display()))
+            }
+            _ => {}
+        }
+    }
+
+    let mut names = HashSet::new();
+    for portfolio in &config.portfolios {
+        if portfolio.name.is_empty() {
+            errors.push("portfolio names must not be empty".to_string());
+        } else if !names.insert(&portfolio.name) {
+            errors.push(format!("portfolio '{}' is defined more than once", portfolio.name));
+        }
+        for address in &portfolio.addresses {
+            if Address::<CurrentNetwork>::from_str(address).is_err() {
+                errors.push(format!("portfolio '{}': '{}' is not a valid address", portfolio.name, address));
+            }
+        }
+        for asset in &portfolio.assets {
+            if Field::<CurrentNetwork>::from_str(asset).is_err() {
+                errors.push(format!("portfolio '{}': '{}' is not a valid token ID", portfolio.name, asset));
+            }
+        }
+        // The assets of a portfolio are attested over just its addresses.
+        if !portfolio.assets.is_empty() && ![1, 2, 4, 8, 16, 32, 64].contains(&portfolio.addresses.len()) {
+            errors.push(format!(
+                "portfolio '{}' has assets, so it needs 1, 2, 4, 8, 16, 32 or 64 addresses, not {}",
+                portfolio.name, portfolio.addresses.len()
+            ));
+        }
+    }
+
+    for webhook in &config.webhooks {
+        if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+            errors.push(format!("webhook '{}' must be an http:// or https:// URL", webhook.url));
+        }
+        if webhook.secret.is_empty() {
+            errors.push(format!("webhook '{}' needs a secret to sign its payloads", webhook.url));
+        }
+        for event in &webhook.events {
+            if !WebhookEvent::NAMES.contains(&event.as_str()) {
+                errors.push(format!(
+                    "webhook '{}': unknown event '{}'; expected one of {}",
+                    webhook.url, event, WebhookEvent::NAMES.join(", ")
+                ));
+            }
+        }
+    }
+
+    for rule in &config.alerts {
+        match rule {
+            AlertRule::Floor { asset, .. } if asset.is_empty() => errors.push("floor alerts need an asset".to_string()),
+            AlertRule::Drop { percent, .. } if !(*percent > 0.0 && *percent <= 100.0) => {
+                errors.push(format!("drop alert percentage {} must be above 0 and at most 100", percent))
+            }
+            AlertRule::Stale { hours: 0 } => errors.push("stale alerts must allow at least 1 hour".to_string()),
+            _ => {}
+        }
+    }
+
+    let sources = match &cli.sources_file {
+        Some(file) => match load_sources(file).await {
+            Ok(sources) if sources.len() > MAX_EXTERNAL_SOURCES => {
+                errors.push(format!("sources file {} lists {} sources, but one attestation commits at most {}", file, sources.len(), MAX_EXTERNAL_SOURCES));
+                Vec::new()
+            }
+            Ok(sources) => sources,
+            Err(e) => {
+                errors.push(format!("failed to load balance sources from {}: {}", file, e));
+                Vec::new()
+            }
+        },
+        None => Vec::new(),
+    };
+    let static_prices = match &cli.static_prices {
+        Some(file) => load_static_prices(file).await
+            .map_err(|e| errors.push(format!("failed to load static prices from {}: {}", file, e)))
+            .ok(),
+        None => None,
+    };
+    let evidence = load_bundle_ids(&cli.evidence_dir).await
+        .map_err(|e| errors.push(format!("failed to read the evidence directory {}: {}", cli.evidence_dir, e)))
+        .unwrap_or_default();
+    let liabilities = match &cli.customer_balances {
+        Some(file) => load_customer_balances(file).await
+            .map_err(|e| format!("failed to load customer balances from {}: {}", file, e))
+            .and_then(|balances| LiabilityTree::new(balances).map_err(|e| format!("failed to build the liabilities tree from {}: {}", file, e)))
+            .map_err(|e| errors.push(e))
+            .ok(),
+        None => None,
+    };
+
+    match errors.is_empty() {
+        true => Ok(Inputs { sources, static_prices, evidence, liabilities }),
+        false => Err(errors),
+    }
+}
+
+/// Adds the addresses of the portfolios that are not tracked yet, returning the addresses that
+/// were added. Their assets stay with the portfolio, since they only cover its addresses.
+pub fn add_portfolios(st: &mut AppState, portfolios: &[Portfolio]) -> Vec<String> {
+    let mut added = Vec::new();
+    for portfolio in portfolios {
+        for address in &portfolio.addresses {
+            if !st.addresses.contains(address) {
+                st.addresses.push(address.clone());
+                added.push(address.clone());
+            }
+        }
+    }
+    added
+}
+
+/// Stops tracking the addresses that were in the previous portfolios but are in none of the
+/// current ones, returning the addresses that were removed. Addresses tracked outside any
+/// portfolio are kept.
+pub fn remove_dropped_portfolios(st: &mut AppState, previous: &[Portfolio], current: &[Portfolio]) -> Vec<String> {
+    let dropped = previous.iter()
+        .flat_map(|portfolio| &portfolio.addresses)
+        .filter(|address| !current.iter().any(|portfolio| portfolio.addresses.contains(address)))
+        .collect::<HashSet<_>>();
+    let removed = st.addresses.iter().filter(|address| dropped.contains(address)).cloned().collect::<Vec<_>>();
+    st.addresses.retain(|address| !dropped.contains(address));
+    removed
+}
+
+/// Splits the credits balances of an attestation by portfolio. Addresses in no portfolio count
+/// as `untagged`, and an address in several portfolios counts towards each.
+pub fn tag_totals(balances: &HashMap<String, u64>, portfolios: &[Portfolio]) -> BTreeMap<String, u128> {
+    let mut totals = BTreeMap::new();
+    for (address, balance) in balances {
+        let mut tagged = false;
+        for portfolio in portfolios.iter().filter(|portfolio| portfolio.addresses.contains(address)) {
+            *totals.entry(portfolio.name.clone()).or_default() += *balance as u128;
+            tagged = true;
+        }
+        if !tagged {
+            *totals.entry("untagged".to_string()).or_default() += *balance as u128;
+        }
+    }
+    totals
+}
+
+/// Reloads the config file on SIGHUP. Endpoints, light verification, schedule, fees, portfolios, webhooks and alerts take effect
+/// right away; the ports, network, storage paths, attestation and price settings only change on restart.
+#[cfg(unix)]
+pub fn spawn_reload_task(base: Cli, matches: ArgMatches, running: Cli, app_state: Arc<RwLock<AppState>>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let Some(path) = base.config.clone() else { return };
+    tokio::spawn(async move {
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => return error!("Failed to listen for SIGHUP: {}", e),
+        };
+        while hangups.recv().await.is_some() {
+            let config = match load_config(&path) {
+                Ok(config) => config,
+                Err(e) => {
+                    error!("Keeping the current settings: {}", e);
+                    continue;
+                }
+            };
+            let mut cli = base.clone();
+            apply_config(&mut cli, &matches, &config);
+            if let Err(errors) = validate(&cli, &config).await {
+                for e in &errors {
+                    error!("Invalid configuration: {}", e);
+                }
+                error!("Keeping the current settings; found {} configuration errors", errors.len());
+                continue;
+            }
+
+            if cli.port != running.port || cli.public_port != running.public_port || cli.network != running.network || storage_paths(&cli) != storage_paths(&running) {
+                warn!("Changes to the ports, network or storage paths take effect on restart");
+            }
+            if cli.mode != running.mode || cli.solvency_threshold != running.solvency_threshold || cli.include_stake != running.include_stake
+                || cli.view_keys != running.view_keys || cli.price_url != running.price_url || cli.price_pointer != running.price_pointer
+                || cli.currency != running.currency {
+                warn!("Changes to the attestation and price settings take effect on restart");
+            }
+
+            let mut st = app_state.write().await;
+            st.endpoint = cli.endpoints[0].clone();
+            st.endpoints = cli.endpoints.clone();
+            st.light_verification = cli.light_verification;
+            st.inclusion_quorum = cli.inclusion_quorum;
+            st.max_attestation_age = cli.max_attestation_age.unwrap_or(2 * cli.cadence);
+            st.priority_fee = cli.priority_fee;
+            st.webhooks = config.webhooks.clone();
+            st.alert_rules = config.alerts.clone();
+            let previous = std::mem::replace(&mut st.portfolios, config.portfolios.clone());
+            let previous_cadence = std::mem::replace(&mut st.cadence, cli.cadence);
+            let removed = remove_dropped_portfolios(&mut st, &previous, &config.portfolios);
+            let added = add_portfolios(&mut st, &config.portfolios);
+            if !added.is_empty() || !removed.is_empty() {
+                let tracked = st.addresses.len();
+                emit(&mut st, WebhookEvent::AddressSetChanged { added, removed, tracked }).await;
+            }
+            // The background task takes the lock too, so release it before waiting on the channel.
+            let task_tx = st.task_tx.clone();
+            drop(st);
+            if cli.cadence != previous_cadence && task_tx.send(BackgroundTaskMsg::SetCadence(cli.cadence)).await.is_err() {
+                error!("Failed to update the cadence; the background task has stopped");
+            }
+            info!("Reloaded configuration from {}", path);
+        }
+    });
+}
+
+/// The storage paths, by their name in the config file.
+fn storage_paths(cli: &Cli) -> [(&'static str, Option<&String>); 17] {
+    [
+        ("addresses", Some(&cli.file)),
+        ("assets", Some(&cli.assets_file)),
+        ("transactions", Some(&cli.transactions_file)),
+        ("liabilities", Some(&cli.liabilities_file)),
+        ("liability_transactions", Some(&cli.liability_transactions_file)),
+        ("records", Some(&cli.records_file)),
+        ("prices", Some(&cli.prices_file)),
+        ("sources", cli.sources_file.as_ref()),
+        ("customer_balances", cli.customer_balances.as_ref()),
+        ("static_prices", cli.static_prices.as_ref()),
+        ("outbox", Some(&cli.outbox_file)),
+        ("pending", Some(&cli.pending_file)),
+        ("alerts", Some(&cli.alerts_file)),
+        ("reports", Some(&cli.reports_file)),
+        ("verifications", Some(&cli.verifications_file)),
+        ("breakdowns", Some(&cli.breakdowns_file)),
+        ("evidence", Some(&cli.evidence_dir)),
+    ]
+}
@@ -0,0 +1,584 @@
+use serde::{Deserialize, Serialize};
+use snarkvm::prelude::{
+    anyhow, bail, ensure, Address, Argument, Block, ConfirmedTransaction, Field, Header, Identifier, Literal,
+    Network, Output, Plaintext, PrivateKey, ProgramID, Ratify, Result, ToBits, Transaction, TransactionsPath, Transition,
+    FINALIZE_ID_DEPTH, FINALIZE_OPERATIONS_DEPTH,
+};
+use snarkvm::synthesizer::program::FinalizeOperation;
+use std::cell::Cell;
+use std::str::FromStr;
+use tracing::{error, warn};
+
+use crate::background::{token_registry, verify_proofs, vm_ready};
+use crate::utilities::{get_attestation_details, get_blocks, AttestationDetails, MappingEntry};
+use crate::{CurrentNetwork, LEGACY_PROGRAM_ID, PROGRAM_ID, SOLVENCY_PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+/// Everything needed to check an attestation offline, without trusting the endpoint it came from.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EvidenceBundle {
+    pub version: u32,
+    pub network: String,
+    pub transaction_id: String,
+    /// The confirmed transaction, with its finalize operations.
+    pub confirmed_transaction: ConfirmedTransaction<CurrentNetwork>,
+    pub block_hash: <CurrentNetwork as Network>::BlockHash,
+    pub previous_hash: <CurrentNetwork as Network>::BlockHash,
+    /// The header of the block containing the transaction.
+    pub header: Header<CurrentNetwork>,
+    /// The path from the transaction ID to the transactions root of the header.
+    pub transactions_path: TransactionsPath<CurrentNetwork>,
+    /// The finalize ID of every transaction in the block, in order. With the ratified finalize ID,
+    /// they hash to the finalize root of the header, which binds the finalize operations to the block.
+    #[serde(default)]
+    pub finalize_ids: Vec<Field<CurrentNetwork>>,
+    /// The finalize ID of the block's ratifications.
+    #[serde(default)]
+    pub ratified_finalize_id: Option<Field<CurrentNetwork>>,
+    /// The attested addresses, in the order the program hashes them.
+    pub addresses: Vec<String>,
+    /// The mapping entry the attestation wrote.
+    pub mapping: MappingEntry,
+    /// The source of `token_registry.aleo`, for token attestations.
+    pub token_registry: Option<String>,
+}
+
+impl EvidenceBundle {
+    pub const VERSION: u32 = 2;
+}
+
+/// Collects the evidence for an accepted attestation from the endpoint.
+pub fn export_bundle(tx_id: &str, private_key: &str, endpoint: &str, network: &str) -> Result<EvidenceBundle> {
+    let details = get_attestation_details(tx_id, private_key, endpoint, network)?;
+    let confirmed_transaction = details.transaction;
+    ensure!(confirmed_transaction.is_accepted(), "Transaction {} was not accepted", tx_id);
+    let height = details.height.parse::<u32>()?;
+    let block = get_blocks::<CurrentNetwork>(height, height + 1, endpoint, network)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Block {} was not found", height))?;
+    let transactions_path = block.transactions().to_path(confirmed_transaction.id())?;
+    let finalize_ids = block.transactions().iter()
+        .map(|transaction| transaction.to_finalize_id())
+        .collect::<Result<Vec<_>>>()?;
+    let ratified_finalize_id = ratified_finalize_id(&block)?;
+    ensure!(
+        finalize_root(&finalize_ids, ratified_finalize_id)? == block.finalize_root(),
+        "The finalize operations of block {} do not hash to its finalize root",
+        height
+    );
+
+    let addresses = match future_arguments(confirmed_transaction.transaction())?.get(2) {
+        Some(Argument::Plaintext(plaintext)) => address_list(plaintext).unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    let token_registry = match details.entry.program_id == TOKEN_PROGRAM_ID {
+        true => Some(token_registry(endpoint).map_err(|e| anyhow!(e))?),
+        false => None,
+    };
+
+    Ok(EvidenceBundle {
+        version: EvidenceBundle::VERSION,
+        network: network.to_string(),
+        transaction_id: tx_id.to_string(),
+        confirmed_transaction,
+        block_hash: block.hash(),
+        previous_hash: block.previous_hash(),
+        header: *block.header(),
+        transactions_path,
+        finalize_ids,
+        ratified_finalize_id: Some(ratified_finalize_id),
+        addresses: addresses.iter().map(|address| address.to_string()).collect(),
+        mapping: details.entry,
+        token_registry,
+    })
+}
+
+/// Runs every check on the bundle, returning the name and outcome of each.
+pub fn verify_bundle(bundle: &EvidenceBundle) -> Vec<(&'static str, Result<()>)> {
+    vec![
+        ("transaction accepted and its ID recomputed", check_transaction(bundle)),
+        ("execution and fee proofs", verify_proofs(bundle.confirmed_transaction.transaction(), || {
+            bundle.token_registry.clone().ok_or_else(|| "The bundle has no token registry source".to_string())
+        }).map_err(|e| anyhow!(e))),
+        ("block hash recomputed from the header", check_block_hash(bundle)),
+        ("transaction included under the transactions root", check_inclusion(bundle)),
+        ("finalize operations committed to by the finalize root", check_finalize_root(bundle)),
+        ("address hash chain matches the data hash", check_hash_chain(bundle)),
+        ("mapping entry written by the transaction", check_mapping(bundle)),
+    ]
+}
+
+fn check_transaction(bundle: &EvidenceBundle) -> Result<()> {
+    let confirmed = &bundle.confirmed_transaction;
+    ensure!(confirmed.is_accepted(), "The transaction was rejected");
+    let transaction = confirmed.transaction();
+    let execution = transaction.execution().ok_or_else(|| anyhow!("The transaction is not an execution"))?;
+    let recomputed = Transaction::from_execution(execution.clone(), transaction.fee_transition())?;
+    ensure!(recomputed.id() == transaction.id(), "The transaction ID does not match its contents");
+    ensure!(transaction.id().to_string() == bundle.transaction_id, "The bundle is for another transaction");
+    Ok(())
+}
+
+fn check_block_hash(bundle: &EvidenceBundle) -> Result<()> {
+    verify_block_hash(&bundle.block_hash, &bundle.previous_hash, &bundle.header)
+}
+
+fn check_inclusion(bundle: &EvidenceBundle) -> Result<()> {
+    verify_transactions_path(&bundle.confirmed_transaction.id(), &bundle.transactions_path, &bundle.header)
+}
+
+fn check_finalize_root(bundle: &EvidenceBundle) -> Result<()> {
+    let ratified_finalize_id = bundle.ratified_finalize_id
+        .ok_or_else(|| anyhow!("The bundle predates version 2 and has no finalize IDs; export it again"))?;
+    let confirmed = &bundle.confirmed_transaction;
+    verify_finalize_id(
+        confirmed.index() as usize,
+        confirmed.to_finalize_id()?,
+        &bundle.finalize_ids,
+        ratified_finalize_id,
+        bundle.header.finalize_root(),
+    )
+    .map_err(|e| anyhow!("{} of block {}", e, bundle.header.height()))
+}
+
+/// Checks that the finalize ID recomputed from the transaction is the one at its index in the block,
+/// and that the block's finalize IDs hash to the finalize root of its header. Without this, the
+/// finalize operations, and so the mapping value, could be edited without breaking any other check.
+fn verify_finalize_id(
+    index: usize,
+    finalize_id: Field<CurrentNetwork>,
+    finalize_ids: &[Field<CurrentNetwork>],
+    ratified_finalize_id: Field<CurrentNetwork>,
+    expected_root: Field<CurrentNetwork>,
+) -> Result<()> {
+    ensure!(
+        finalize_ids.get(index) == Some(&finalize_id),
+        "The finalize operations are not the ones transaction {} committed to",
+        index
+    );
+    ensure!(
+        finalize_root(finalize_ids, ratified_finalize_id)? == expected_root,
+        "The finalize IDs do not hash to the finalize root"
+    );
+    Ok(())
+}
+
+/// The finalize root over the finalize IDs of the transactions and of the ratifications, as snarkVM
+/// computes it.
+fn finalize_root(finalize_ids: &[Field<CurrentNetwork>], ratified_finalize_id: Field<CurrentNetwork>) -> Result<Field<CurrentNetwork>> {
+    let leaves = finalize_ids.iter()
+        .chain(std::iter::once(&ratified_finalize_id))
+        .map(|id| id.to_bits_le())
+        .collect::<Vec<_>>();
+    Ok(*CurrentNetwork::merkle_tree_bhp::<FINALIZE_OPERATIONS_DEPTH>(&leaves)?.root())
+}
+
+/// The finalize ID of the finalize operations, as `ConfirmedTransaction::to_finalize_id` computes it.
+fn finalize_id(operations: &[FinalizeOperation<CurrentNetwork>]) -> Result<Field<CurrentNetwork>> {
+    let leaves = operations.iter().map(|operation| operation.to_bits_le()).collect::<Vec<_>>();
+    Ok(*CurrentNetwork::merkle_tree_bhp::<FINALIZE_ID_DEPTH>(&leaves)?.root())
+}
+
+/// Recomputes the finalize ID of the block's ratifications. The block reward replaces the committee,
+/// delegated and bonded mappings of `credits.aleo`, which only depends on their IDs. Prover rewards
+/// update balances that the block does not carry, so those blocks cannot be bound this way.
+fn ratified_finalize_id(block: &Block<CurrentNetwork>) -> Result<Field<CurrentNetwork>> {
+    let program_id = ProgramID::<CurrentNetwork>::from_str("credits.aleo")?;
+    let mut operations = Vec::new();
+    for ratify in block.ratifications().iter() {
+        match ratify {
+            Ratify::Genesis(..) => bail!("Cannot bind the finalize operations of the genesis block"),
+            Ratify::BlockReward(_) => {
+                for mapping in ["committee", "delegated", "bonded"] {
+                    let mapping_id = to_mapping_id(&program_id, &Identifier::from_str(mapping)?)?;
+                    operations.push(FinalizeOperation::ReplaceMapping(mapping_id));
+                }
+            }
+            Ratify::PuzzleReward(reward) => {
+                let solutions = block.solutions().as_ref().map_or(0, |solutions| solutions.len());
+                ensure!(
+                    *reward == 0 || solutions == 0,
+                    "Block {} pays prover rewards, whose finalize operations cannot be recomputed from the block",
+                    block.height()
+                );
+            }
+        }
+    }
+    finalize_id(&operations)
+}
+
+/// Checks that the header, chained to the previous block, hashes to the block hash.
+fn verify_block_hash(
+    block_hash: &<CurrentNetwork as Network>::BlockHash,
+    previous_hash: &<CurrentNetwork as Network>::BlockHash,
+    header: &Header<CurrentNetwork>,
+) -> Result<()> {
+    let preimage = (**previous_hash, header.to_root()?).to_bits_le();
+    ensure!(CurrentNetwork::hash_bhp1024(&preimage)? == **block_hash, "The header does not hash to block {}", block_hash);
+    Ok(())
+}
+
+/// Checks that the path leads from the transaction ID to the transactions root of the header.
+fn verify_transactions_path(
+    transaction_id: &<CurrentNetwork as Network>::TransactionID,
+    path: &TransactionsPath<CurrentNetwork>,
+    header: &Header<CurrentNetwork>,
+) -> Result<()> {
+    ensure!(
+        CurrentNetwork::verify_merkle_path_bhp(path, &header.transactions_root(), &transaction_id.to_bits_le()),
+        "The transaction is not under the transactions root of block {}",
+        header.height()
+    );
+    Ok(())
+}
+
+/// The outcome of checking the block of an attestation against the endpoints, in light verification mode.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Inclusion {
+    pub included: bool,
+    pub block_hash: Option<String>,
+    /// The endpoints that returned the block.
+    pub endpoints: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Checks the height and timestamp the endpoint reported the way a light client would, rather than
+/// taking them on faith: at least `quorum` endpoints, or all of them if unset, must return the same
+/// block at that height, its header must hash to the block hash, and a Merkle path must lead from the
+/// transaction to its transactions root.
+///
+/// Fails without an outcome when too few endpoints return the block, since that says nothing about
+/// the attestation and the check should run again.
+pub fn check_block_inclusion(details: &AttestationDetails, endpoints: &[String], quorum: Option<usize>, network: &str) -> Result<Inclusion> {
+    let height = details.height.parse::<u32>()?;
+    let mut blocks = Vec::new();
+    let mut missing = Vec::new();
+    for endpoint in endpoints {
+        match get_blocks::<CurrentNetwork>(height, height + 1, endpoint, network) {
+            Ok(fetched) => match fetched.into_iter().next() {
+                Some(block) => blocks.push((endpoint.clone(), block)),
+                None => missing.push(format!("{} has no block {}", endpoint, height)),
+            },
+            Err(e) => missing.push(format!("{} is unreachable: {}", endpoint, e)),
+        }
+    }
+    let quorum = quorum.unwrap_or(endpoints.len()).max(1);
+    if blocks.len() < quorum {
+        error!("Block inclusion check for {} reached {} of the {} endpoints it needs: {}", details.transaction.id(), blocks.len(), quorum, missing.join("; "));
+        bail!("Only {} of the {} endpoints needed returned block {} ({})", blocks.len(), quorum, height, missing.join("; "));
+    }
+    for missing in &missing {
+        warn!("Leaving an endpoint out of the inclusion check: {}", missing);
+    }
+
+    let mut agreed = Vec::new();
+    let result = (|| {
+        let (_, block) = &blocks[0];
+        if blocks.iter().any(|(_, other)| other.hash() != block.hash()) {
+            let hashes = blocks.iter().map(|(endpoint, block)| format!("{} says {}", endpoint, block.hash())).collect::<Vec<_>>();
+            bail!("The endpoints disagree on block {}: {}", height, hashes.join(", "));
+        }
+        agreed = blocks.iter().map(|(endpoint, _)| endpoint.clone()).collect();
+
+        verify_block_hash(&block.hash(), &block.previous_hash(), block.header())?;
+        let transaction_id = details.transaction.id();
+        verify_transactions_path(&transaction_id, &block.transactions().to_path(transaction_id)?, block.header())?;
+        ensure!(block.height() == height, "The endpoints returned block {} for height {}", block.height(), height);
+        ensure!(
+            block.timestamp() == details.unix_timestamp,
+            "The endpoint reported timestamp {} but the block header says {}", details.unix_timestamp, block.timestamp()
+        );
+        Ok(block.hash().to_string())
+    })();
+
+    if let Err(e) = &result {
+        error!("Block inclusion check failed for {}: {}", details.transaction.id(), e);
+    }
+    Ok(Inclusion {
+        included: result.is_ok(),
+        block_hash: result.as_ref().ok().cloned(),
+        endpoints: agreed,
+        error: result.err().map(|e| e.to_string()),
+    })
+}
+
+fn check_hash_chain(bundle: &EvidenceBundle) -> Result<()> {
+    let arguments = future_arguments(bundle.confirmed_transaction.transaction())?;
+    let listed = match arguments.get(2) {
+        Some(Argument::Plaintext(plaintext)) => address_list(plaintext),
+        _ => None,
+    };
+    // Only attestations over an address list hash their addresses.
+    let Some(listed) = listed else {
+        ensure!(bundle.addresses.is_empty(), "The attestation does not cover an address list");
+        return Ok(());
+    };
+    let addresses = bundle.addresses.iter()
+        .map(|address| Address::<CurrentNetwork>::from_str(address))
+        .collect::<Result<Vec<_>>>()?;
+    ensure!(addresses == listed, "The addresses do not match the ones the transaction attests");
+    let data_hash = match arguments.first() {
+        Some(Argument::Plaintext(Plaintext::Literal(Literal::Field(hash), _))) => *hash,
+        _ => bail!("The transaction has no data hash"),
+    };
+    ensure!(hash_chain(&addresses)? == data_hash, "The addresses do not hash to the data hash {}", data_hash);
+    Ok(())
+}
+
+fn check_mapping(bundle: &EvidenceBundle) -> Result<()> {
+    let mapping = &bundle.mapping;
+    let transition = last_transition(bundle.confirmed_transaction.transaction())?;
+    ensure!(transition.program_id().to_string() == mapping.program_id, "The mapping belongs to another program");
+
+    // The key must be the one for this attestation: its data hash, prover and block height.
+    let key = Plaintext::<CurrentNetwork>::from_str(&mapping.key)?;
+    let arguments = future_arguments(bundle.confirmed_transaction.transaction())?;
+    let member = |name: &str| match &key {
+        Plaintext::Struct(members, _) => Identifier::from_str(name).ok().and_then(|name| members.get(&name).cloned()),
+        _ => None,
+    };
+    for (name, index) in [("hash", 0), ("user", 1)] {
+        let argument = match arguments.get(index) {
+            Some(Argument::Plaintext(plaintext)) => Some(plaintext.clone()),
+            _ => None,
+        };
+        ensure!(member(name).is_some() && member(name) == argument, "The mapping key has the wrong {}", name);
+    }
+    ensure!(
+        member("height").map(|height| height.to_string()) == Some(format!("{}u32", bundle.header.height())),
+        "The mapping key is not for block {}",
+        bundle.header.height()
+    );
+
+    check_written(bundle.confirmed_transaction.finalize_operations(), mapping, &key)
+}
+
+/// Checks that the finalize operations write the mapping entry. They commit to each key and value
+/// written, by hash.
+fn check_written(operations: &[FinalizeOperation<CurrentNetwork>], mapping: &MappingEntry, key: &Plaintext<CurrentNetwork>) -> Result<()> {
+    let program_id = ProgramID::<CurrentNetwork>::from_str(&mapping.program_id)?;
+    let mapping_name = Identifier::<CurrentNetwork>::from_str(&mapping.mapping)?;
+    let value = Plaintext::<CurrentNetwork>::from_str(&mapping.value)?;
+    let mapping_id = to_mapping_id(&program_id, &mapping_name)?;
+    let key_id = to_key_id(&program_id, &mapping_name, key)?;
+    let value_id = CurrentNetwork::hash_bhp1024(&(key_id, CurrentNetwork::hash_bhp1024(&value.to_bits_le())?).to_bits_le())?;
+    let written = operations.iter().any(|operation| match operation {
+        FinalizeOperation::InsertKeyValue(m, k, v) | FinalizeOperation::UpdateKeyValue(m, k, v) => {
+            (*m, *k, *v) == (mapping_id, key_id, value_id)
+        }
+        _ => false,
+    });
+    ensure!(written, "The transaction did not write {} to {}/{}", mapping.value, mapping.program_id, mapping.mapping);
+    Ok(())
+}
+
+/// The outcome of verifying a fetched attestation locally, stored with the attestation.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Verification {
+    pub verified: bool,
+    /// Unix time of the check.
+    pub checked: i64,
+    pub error: Option<String>,
+    /// The block inclusion check, if light verification is on.
+    #[serde(default)]
+    pub inclusion: Option<Inclusion>,
+}
+
+impl Verification {
+    /// Whether the proofs verified and, if it was checked, the block inclusion held.
+    pub fn passed(&self) -> bool {
+        self.verified && self.inclusion.as_ref().is_none_or(|inclusion| inclusion.included)
+    }
+}
+
+/// Whether the function is one the tracker attests with, ignoring the size of its address array.
+fn is_attestation_function(program_id: &str, function: &str) -> bool {
+    let name = function.trim_end_matches(|c: char| c.is_ascii_digit());
+    match program_id {
+        PROGRAM_ID => [
+            "record_balances",
+            "record_stake_balances",
+            "record_private_balances",
+            "record_external_balances",
+            "record_liabilities",
+        ].contains(&name),
+        LEGACY_PROGRAM_ID => name == "record_balances",
+        SOLVENCY_PROGRAM_ID => name == "prove_solvency",
+        TOKEN_PROGRAM_ID => name == "record_token_balances",
+        _ => false,
+    }
+}
+
+/// Verifies an attestation fetched from the endpoint without trusting it: the root transition must
+/// call an attestation function with the prover as the caller, and the proofs must verify locally.
+/// The source of `token_registry.aleo` is only asked for to verify a token attestation.
+pub fn verify_attestation(
+    transaction: &Transaction<CurrentNetwork>,
+    private_key: &str,
+    registry: impl FnOnce() -> Result<String, String>,
+) -> Result<()> {
+    let transition = last_transition(transaction)?;
+    let (program_id, function) = (transition.program_id().to_string(), transition.function_name().to_string());
+    ensure!(is_attestation_function(&program_id, &function), "{}/{} is not an attestation function", program_id, function);
+
+    // Every attestation function passes `self.caller` as the second argument of its future.
+    let prover = Address::try_from(PrivateKey::<CurrentNetwork>::from_str(private_key)?)?;
+    match future_arguments(transaction)?.get(1) {
+        Some(Argument::Plaintext(Plaintext::Literal(Literal::Address(caller), _))) => {
+            ensure!(*caller == prover, "The caller {} is not the prover {}", caller, prover)
+        }
+        _ => bail!("The transaction does not record its caller"),
+    }
+
+    verify_proofs(transaction, registry).map_err(|e| anyhow!(e))
+}
+
+/// Verifies an attestation and records the outcome. Fails without an outcome while the VM is
+/// initializing or when the token registry cannot be fetched, since neither says anything about
+/// the attestation, so the check can run again later.
+pub fn check_attestation(transaction: &Transaction<CurrentNetwork>, private_key: &str, endpoint: &str) -> Result<Verification, String> {
+    if !vm_ready() {
+        return Err("the VM is still initializing".to_string());
+    }
+    let registry_error = Cell::new(None);
+    let result = verify_attestation(transaction, private_key, || {
+        token_registry(endpoint).inspect_err(|e| registry_error.set(Some(e.clone())))
+    });
+    if let Some(e) = registry_error.take() {
+        return Err(e);
+    }
+    if let Err(e) = &result {
+        warn!("Attestation {} failed local verification: {}", transaction.id(), e);
+    }
+    Ok(Verification {
+        verified: result.is_ok(),
+        checked: chrono::Utc::now().timestamp(),
+        error: result.err().map(|e| e.to_string()),
+        inclusion: None,
+    })
+}
+
+fn last_transition(transaction: &Transaction<CurrentNetwork>) -> Result<&Transition<CurrentNetwork>> {
+    transaction.execution()
+        .and_then(|execution| execution.transitions().last())
+        .ok_or_else(|| anyhow!("The transaction has no transitions"))
+}
+
+/// The arguments of the future output by the root transition.
+fn future_arguments(transaction: &Transaction<CurrentNetwork>) -> Result<Vec<Argument<CurrentNetwork>>> {
+    match last_transition(transaction)?.outputs().first() {
+        Some(Output::Future(_, Some(future))) => Ok(future.arguments().to_vec()),
+        _ => bail!("The transaction has no future"),
+    }
+}
+
+/// The addresses of an address array argument, flattening the two rows of the 64 variant.
+fn address_list(plaintext: &Plaintext<CurrentNetwork>) -> Option<Vec<Address<CurrentNetwork>>> {
+    match plaintext {
+        Plaintext::Literal(Literal::Address(address), _) => Some(vec![*address]),
+        Plaintext::Array(elements, _) => elements.iter()
+            .map(address_list)
+            .collect::<Option<Vec<_>>>()
+            .map(|rows| rows.concat()),
+        _ => None,
+    }
+}
+
+/// Recomputes the data hash the programs derive from the addresses, `BHP512(Parent { left, right })`
+/// over each address in turn. The 64 variant walks its two rows column by column.
+pub fn hash_chain(addresses: &[Address<CurrentNetwork>]) -> Result<Field<CurrentNetwork>> {
+    let order: Vec<&Address<CurrentNetwork>> = match addresses.len() {
+        64 => (0..32).flat_map(|i| [&addresses[i], &addresses[32 + i]]).collect(),
+        _ => addresses.iter().collect(),
+    };
+    order.into_iter().try_fold(Field::from_u8(0), |last_hash, address| {
+        let parent = Plaintext::<CurrentNetwork>::from_str(&format!("{{ left: {last_hash}, right: {address} }}"))?;
+        CurrentNetwork::hash_bhp512(&parent.to_bits_le())
+    })
+}
+
+/// The mapping ID committed to by finalize operations, as snarkVM computes it.
+fn to_mapping_id(program_id: &ProgramID<CurrentNetwork>, mapping_name: &Identifier<CurrentNetwork>) -> Result<Field<CurrentNetwork>> {
+    let mut preimage = Vec::new();
+    program_id.write_bits_le(&mut preimage);
+    false.write_bits_le(&mut preimage);
+    mapping_name.write_bits_le(&mut preimage);
+    CurrentNetwork::hash_bhp1024(&preimage)
+}
+
+/// The key ID committed to by finalize operations, as snarkVM computes it.
+fn to_key_id(
+    program_id: &ProgramID<CurrentNetwork>,
+    mapping_name: &Identifier<CurrentNetwork>,
+    key: &Plaintext<CurrentNetwork>,
+) -> Result<Field<CurrentNetwork>> {
+    let mut preimage = Vec::new();
+    program_id.write_bits_le(&mut preimage);
+    false.write_bits_le(&mut preimage);
+    mapping_name.write_bits_le(&mut preimage);
+    false.write_bits_le(&mut preimage);
+    key.write_bits_le(&mut preimage);
+    CurrentNetwork::hash_bhp1024(&preimage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(value: &str) -> MappingEntry {
+        MappingEntry {
+            program_id: PROGRAM_ID.to_string(),
+            mapping: "data".to_string(),
+            key: "{ hash: 1field, user: aleo1rhgdu77hgyqd3xjj8ucu3jj9r2krwz6mnzyd80gncr5fxcwlh5rsvzp9px, height: 42u32 }".to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    /// The operation that writes the entry, as finalize records it.
+    fn insert(entry: &MappingEntry) -> FinalizeOperation<CurrentNetwork> {
+        let program_id = ProgramID::from_str(&entry.program_id).unwrap();
+        let mapping = Identifier::from_str(&entry.mapping).unwrap();
+        let key = Plaintext::from_str(&entry.key).unwrap();
+        let key_id = to_key_id(&program_id, &mapping, &key).unwrap();
+        let value = Plaintext::<CurrentNetwork>::from_str(&entry.value).unwrap();
+        let value_id = CurrentNetwork::hash_bhp1024(
+            &(key_id, CurrentNetwork::hash_bhp1024(&value.to_bits_le()).unwrap()).to_bits_le()
+        ).unwrap();
+        FinalizeOperation::InsertKeyValue(to_mapping_id(&program_id, &mapping).unwrap(), key_id, value_id)
+    }
+
+    #[test]
+    fn a_changed_mapping_value_is_rejected() {
+        let original = entry("{ total: 1000u128 }");
+        let operations = vec![insert(&original)];
+        let key = Plaintext::from_str(&original.key).unwrap();
+        let finalize_ids = vec![Field::from_u8(7), finalize_id(&operations).unwrap(), Field::from_u8(9)];
+        let ratified = Field::from_u8(3);
+        let root = finalize_root(&finalize_ids, ratified).unwrap();
+        check_written(&operations, &original, &key).unwrap();
+        verify_finalize_id(1, finalize_id(&operations).unwrap(), &finalize_ids, ratified, root).unwrap();
+
+        // Editing only the value leaves it unwritten by the transaction.
+        let edited = entry("{ total: 9000u128 }");
+        assert!(check_written(&operations, &edited, &key).is_err());
+
+        // Editing the finalize operations to match breaks the binding to the block.
+        let forged = vec![insert(&edited)];
+        check_written(&forged, &edited, &key).unwrap();
+        assert!(verify_finalize_id(1, finalize_id(&forged).unwrap(), &finalize_ids, ratified, root).is_err());
+
+        // So does editing the finalize IDs along with them.
+        let mut forged_ids = finalize_ids.clone();
+        forged_ids[1] = finalize_id(&forged).unwrap();
+        assert!(verify_finalize_id(1, finalize_id(&forged).unwrap(), &forged_ids, ratified, root).is_err());
+    }
+
+    #[test]
+    fn the_finalize_id_must_be_at_the_transaction_index() {
+        let operations = vec![insert(&entry("{ total: 1000u128 }"))];
+        let finalize_ids = vec![finalize_id(&operations).unwrap(), Field::from_u8(9)];
+        let root = finalize_root(&finalize_ids, Field::from_u8(3)).unwrap();
+        assert!(verify_finalize_id(1, finalize_id(&operations).unwrap(), &finalize_ids, Field::from_u8(3), root).is_err());
+        assert!(verify_finalize_id(0, finalize_id(&operations).unwrap(), &finalize_ids, Field::from_u8(4), root).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+use snarkvm::prelude::{Field, FromBits, Network, SizeInDataBits, ToBits, Zero};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use crate::state::LiabilityTransaction;
+use crate::utilities::{get_attestation_details, AttestationResult};
+use crate::CurrentNetwork;
+
+/// A single customer balance, as ingested from the customer balance file.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CustomerBalance {
+    /// The customer's account identifier.
+    pub id: String,
+    /// The secret salt shared with the customer, used to blind the identifier.
+    pub salt: String,
+    /// The customer's balance in microcredits.
+    pub balance: u64,
+}
+
+/// The published commitment to the liabilities tree.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LiabilityCommitment {
+    pub root: String,
+    pub total: u64,
+    pub customers: usize,
+    pub created: String,
+}
+
+/// The liabilities commitment as anchored on-chain by a `record_liabilities` transaction.
+#[derive(Clone, Debug)]
+pub struct AnchoredCommitment {
+    pub transaction_id: String,
+    pub height: String,
+    pub root: String,
+    pub total: u64,
+    /// The reserves attestation broadcast in the same run, if it was recorded.
+    pub reserves: Option<String>,
+}
+
+/// Reads the newest liabilities commitment that landed on-chain, from the root the transaction passed
+/// and the total it wrote to the `liabilities` mapping. Commitments that are still pending or were
+/// rejected are skipped; an error is returned only if none could be read and one failed to resolve.
+pub fn anchored_commitment(
+    transactions: &[LiabilityTransaction],
+    private_key: &str,
+    endpoint: &str,
+    network: &str,
+) -> Result<Option<AnchoredCommitment>, String> {
+    let mut error = None;
+    for transaction in transactions.iter().rev() {
+        let details = match get_attestation_details(&transaction.id, private_key, endpoint, network) {
+            Ok(details) if details.transaction.is_accepted() => details,
+            Ok(_) => continue,
+            Err(e) => {
+                error.get_or_insert(format!("Failed to resolve liabilities commitment {}: {}", transaction.id, e));
+                continue;
+            }
+        };
+        let total = match &details.result {
+            AttestationResult::Total(total) => total.trim_end_matches("u64").parse::<u64>()
+                .map_err(|e| format!("Invalid liabilities total '{}': {}", total, e))?,
+            other => return Err(format!("Unexpected liabilities commitment result: {}", other)),
+        };
+        return Ok(Some(AnchoredCommitment {
+            transaction_id: transaction.id.clone(),
+            height: details.height,
+            root: details.data_hash,
+            total,
+            reserves: transaction.reserves.clone(),
+        }));
+    }
+    error.map_or(Ok(None), Err)
+}
+
+/// A sibling node along the path from a leaf to the root.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ProofNode {
+    pub hash: String,
+    pub sum: u64,
+}
+
+/// A Merkle-sum inclusion proof for a single customer.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct InclusionProof {
+    pub index: usize,
+    pub balance: u64,
+    pub siblings: Vec<ProofNode>,
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+    hash: Field<CurrentNetwork>,
+    sum: u64,
+}
+
+/// A Merkle-sum tree over the hashed customer identifiers and balances.
+pub struct LiabilityTree {
+    customers: Vec<CustomerBalance>,
+    levels: Vec<Vec<Node>>,
+}
+
+/// Hashes a customer identifier with its salt, so the tree does not reveal who the customers are.
+pub fn hash_customer_id(id: &str, salt: &str) -> Result<Field<CurrentNetwork>, String> {
+    let mut bytes = (salt.len() as u32).to_le_bytes().to_vec();
+    bytes.extend_from_slice(salt.as_bytes());
+    bytes.extend_from_slice(id.as_bytes());
+    let digest = CurrentNetwork::hash_sha3_256(&bytes.to_bits_le())
+        .map_err(|e| format!("Failed to hash customer id: {}", e))?;
+    Field::from_bits_le(&digest[..Field::<CurrentNetwork>::size_in_data_bits()])
+        .map_err(|e| format!("Failed to hash customer id: {}", e))
+}
+
+fn hash_leaf(id_hash: Field<CurrentNetwork>, balance: u64) -> Result<Node, String> {
+    let hash = CurrentNetwork::hash_psd2(&[id_hash, Field::from_u64(balance)])
+        .map_err(|e| format!("Failed to hash leaf: {}", e))?;
+    Ok(Node { hash, sum: balance })
+}
+
+fn hash_children(left: &Node, right: &Node) -> Result<Node, String> {
+    let sum = left.sum.checked_add(right.sum)
+        .ok_or_else(|| "Liabilities total overflows u64".to_string())?;
+    let hash = CurrentNetwork::hash_psd4(&[left.hash, Field::from_u64(left.sum), right.hash, Field::from_u64(right.sum)])
+        .map_err(|e| format!("Failed to hash node: {}", e))?;
+    Ok(Node { hash, sum })
+}
+
+impl LiabilityTree {
+    /// Builds the tree, padding the leaves up to the next power of two with empty nodes. Each customer
+    /// id may appear once, as a repeated id would be proven against only one of its balances.
+    pub fn new(customers: Vec<CustomerBalance>) -> Result<Self, String> {
+        if customers.is_empty() {
+            return Err("Customer balance file is empty".to_string());
+        }
+        let mut ids = HashSet::new();
+        if let Some(duplicate) = customers.iter().find(|c| !ids.insert(c.id.as_str())) {
+            return Err(format!("Customer id '{}' appears more than once", duplicate.id));
+        }
+        let mut leaves = customers.iter()
+            .map(|c| hash_leaf(hash_customer_id(&c.id, &c.salt)?, c.balance))
+            .collect::<Result<Vec<_>, _>>()?;
+        leaves.resize(leaves.len().next_power_of_two(), Node { hash: Field::zero(), sum: 0 });
+
+        let mut levels = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels.last().unwrap()
+                .chunks(2)
+                .map(|pair| hash_children(&pair[0], &pair[1]))
+                .collect::<Result<Vec<_>, _>>()?;
+            levels.push(next);
+        }
+
+        Ok(Self { customers, levels })
+    }
+
+    pub fn root(&self) -> Field<CurrentNetwork> {
+        self.levels.last().unwrap()[0].hash
+    }
+
+    pub fn total(&self) -> u64 {
+        self.levels.last().unwrap()[0].sum
+    }
+
+    pub fn num_customers(&self) -> usize {
+        self.customers.len()
+    }
+
+    /// Returns the commitment to publish for this tree.
+    pub fn commitment(&self) -> LiabilityCommitment {
+        LiabilityCommitment {
+            root: self.root().to_string(),
+            total: self.total(),
+            customers: self.num_customers(),
+            created: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Generates the inclusion proof for the customer with the given identifier and salt.
+    pub fn proof(&self, id: &str, salt: &str) -> Option<InclusionProof> {
+        let index = self.customers.iter().position(|c| c.id == id && c.salt == salt)?;
+        let siblings = self.levels[..self.levels.len() - 1].iter()
+            .enumerate()
+            .map(|(depth, level)| {
+                let sibling = level[(index >> depth) ^ 1];
+                ProofNode { hash: sibling.hash.to_string(), sum: sibling.sum }
+            })
+            .collect();
+        Some(InclusionProof { index, balance: self.customers[index].balance, siblings })
+    }
+}
+
+impl InclusionProof {
+    /// Recomputes the root from the customer's identifier, salt and balance, and checks it
+    /// against the published root and total.
+    pub fn verify(&self, id: &str, salt: &str, root: &str, total: u64) -> Result<bool, String> {
+        let root = Field::<CurrentNetwork>::from_str(root)
+            .map_err(|e| format!("Failed to parse root: {}", e))?;
+        let mut node = hash_leaf(hash_customer_id(id, salt)?, self.balance)?;
+        for (depth, sibling) in self.siblings.iter().enumerate() {
+            let sibling = Node {
+                hash: Field::from_str(&sibling.hash).map_err(|e| format!("Failed to parse sibling: {}", e))?,
+                sum: sibling.sum,
+            };
+            node = match (self.index >> depth) & 1 {
+                0 => hash_children(&node, &sibling)?,
+                _ => hash_children(&sibling, &node)?,
+            };
+        }
+        Ok(node.hash == root && node.sum == total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn customer(id: &str, balance: u64) -> CustomerBalance {
+        CustomerBalance { id: id.to_string(), salt: format!("salt-{}", id), balance }
+    }
+
+    #[test]
+    fn every_proof_verifies_against_the_root() {
+        let customers = vec![customer("alice", 5), customer("bob", 7), customer("carol", 11)];
+        let tree = LiabilityTree::new(customers.clone()).unwrap();
+        let root = tree.root().to_string();
+        assert_eq!(tree.total(), 23);
+        for c in &customers {
+            let proof = tree.proof(&c.id, &c.salt).unwrap();
+            assert_eq!(proof.balance, c.balance);
+            assert!(proof.verify(&c.id, &c.salt, &root, tree.total()).unwrap());
+        }
+    }
+
+    #[test]
+    fn proofs_do_not_verify_with_other_inputs() {
+        let tree = LiabilityTree::new(vec![customer("alice", 5), customer("bob", 7)]).unwrap();
+        let root = tree.root().to_string();
+        let proof = tree.proof("alice", "salt-alice").unwrap();
+        assert!(!proof.verify("alice", "salt-bob", &root, 12).unwrap());
+        assert!(!proof.verify("alice", "salt-alice", &root, 13).unwrap());
+        let mut inflated = proof.clone();
+        inflated.balance += 1;
+        assert!(!inflated.verify("alice", "salt-alice", &root, 12).unwrap());
+        assert!(tree.proof("alice", "salt-bob").is_none());
+    }
+
+    #[test]
+    fn pads_the_leaves_to_a_power_of_two() {
+        let tree = LiabilityTree::new((0..5).map(|i| customer(&i.to_string(), 1)).collect()).unwrap();
+        assert_eq!(tree.levels[0].len(), 8);
+        assert_eq!(tree.num_customers(), 5);
+        assert_eq!(tree.total(), 5);
+        let proof = tree.proof("4", "salt-4").unwrap();
+        assert_eq!(proof.siblings.len(), 3);
+        assert!(proof.verify("4", "salt-4", &tree.root().to_string(), 5).unwrap());
+    }
+
+    #[test]
+    fn rejects_duplicate_ids() {
+        let customers = vec![customer("alice", 5), customer("bob", 7), CustomerBalance { salt: "other".to_string(), ..customer("alice", 9) }];
+        assert!(LiabilityTree::new(customers).err().is_some_and(|e| e.contains("alice")));
+    }
+
+    #[test]
+    fn rejects_totals_that_overflow() {
+        assert!(LiabilityTree::new(vec![customer("alice", u64::MAX), customer("bob", 1)]).is_err());
+    }
+
+    #[test]
+    fn rejects_an_empty_file() {
+        assert!(LiabilityTree::new(Vec::new()).is_err());
+    }
+}
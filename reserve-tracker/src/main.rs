@@ -1,81 +1,533 @@
-use axum::{Router, routing::{get, post}};
-use clap::Parser;
+use axum::{Router, routing::{delete, get, post}};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
-use snarkvm::prelude::TestnetV0;
+use snarkvm::prelude::{anyhow, TestnetV0};
 use tokio::sync::{RwLock, mpsc};
 use crate::{
-    storage::{load_addresses, save_addresses},
-    state::AppState,
-    background::{BackgroundTaskMsg, spawn_background_task},
+    storage::{load_addresses, save_addresses, load_assets, save_assets},
+    state::{AppState, Mode, TrackedTransaction, TransactionKind},
+    background::{BackgroundTaskMsg, spawn_background_task, spawn_confirmation_task},
 };
-use crate::routes::{get_form, get_transactions_page, handle_form, handle_transactions_form};
-use crate::storage::load_transactions;
+use crate::routes::{get_form, get_transactions_page, handle_form, handle_transactions_form, get_liabilities_page, handle_liabilities_form, get_inclusion_page, handle_inclusion_form, get_metrics, get_healthz, get_readyz, get_report, get_evidence, get_public_evidence, get_public_page, get_atom_feed, get_badge, get_widget};
+use crate::routes::{api_add_address, api_get_attestation, api_get_config, api_get_run, api_list_addresses, api_list_attestations, api_list_runs, api_remove_address, api_trigger_run};
+use crate::storage::{load_transactions, load_records, load_prices, load_outbox, load_pending, load_alerts, load_reports, load_verifications, load_breakdowns, save_liability_commitment};
+use crate::prices::{HttpJsonFeed, PriceFeed, StaticFileFeed};
+use crate::records::RecordStore;
+use crate::metrics::METRICS;
+use crate::telemetry::LogFormat;
+use crate::evidence::{verify_bundle, EvidenceBundle};
+use crate::reports::SignedReport;
+use crate::site::export_site;
+use crate::webhooks::spawn_delivery_task;
+use crate::config::{add_portfolios, apply_config, load_config, validate, FileConfig};
+#[cfg(unix)]
+use crate::config::spawn_reload_task;
+use tracing::{error, info, warn};
 
 mod state;
 mod storage;
 mod background;
 mod routes;
 mod utilities;
+mod liabilities;
+mod records;
+mod sources;
+mod prices;
+mod metrics;
+mod telemetry;
+mod config;
+mod webhooks;
+mod alerts;
+mod reports;
+mod evidence;
+mod site;
 
 pub type CurrentNetwork = TestnetV0;
 pub type CurrentAleo = snarkvm::circuit::AleoTestnetV0;
 
 pub const NETWORK_NAME: &str = "testnet";
+/// The reserves program with the functions added after the first: the liabilities commitment and
+/// the private, stake and external balances.
+pub const PROGRAM_ID: &str = "proof_of_reserves_v0_2_0.aleo";
+/// The first reserves program, which only has `record_balances`. Plain balances are still attested with it.
+pub const LEGACY_PROGRAM_ID: &str = "proof_of_reserves_v0_1_0.aleo";
+pub const SOLVENCY_PROGRAM_ID: &str = "proof_of_solvency_v0_1_0.aleo";
+pub const TOKEN_PROGRAM_ID: &str = "proof_of_token_reserves_v0_1_0.aleo";
 
 /// Command-line options
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(name = "reserve-tracker", version, about = "Address tracking server")]
 struct Cli {
+    /// TOML config file; flags and environment variables take precedence over it (optional)
+    #[arg(long, env = "RESERVE_TRACKER_CONFIG")]
+    config: Option<String>,
+
     /// Port to run the server on
-    #[arg(short, long, default_value_t = 3000)]
+    #[arg(short, long, env = "RESERVE_TRACKER_PORT", default_value_t = 3000)]
     port: u16,
 
+    /// Port to serve the read-only public transparency site on (optional)
+    #[arg(long, env = "RESERVE_TRACKER_PUBLIC_PORT")]
+    public_port: Option<u16>,
+
     /// File to store the addresses
-    #[arg(short, long, default_value = "addresses.json")]
+    #[arg(short, long, env = "RESERVE_TRACKER_ADDRESSES_FILE", default_value = "addresses.json")]
     file: String,
 
+    /// File to store the token IDs of the assets attested alongside credits
+    #[arg(long, env = "RESERVE_TRACKER_ASSETS_FILE", default_value = "assets.json")]
+    assets_file: String,
+
     /// File to store the transactions
-    #[arg(short, long, default_value = "transactions.json")]
+    #[arg(short, long, env = "RESERVE_TRACKER_TRANSACTIONS_FILE", default_value = "transactions.json")]
     transactions_file: String,
 
     /// Cadence (in seconds) for background task
-    #[arg(long, default_value_t = 12000)]
+    #[arg(long, env = "RESERVE_TRACKER_CADENCE", default_value_t = 12000)]
     cadence: u64,
 
     /// Private key (required)
-    #[arg(long)]
+    #[arg(long, env = "RESERVE_TRACKER_PRIVATE_KEY", hide_env_values = true)]
     private_key: String,
 
-    /// Endpoint (optional, with default)
-    #[arg(long, default_value = "https://api.explorer.provable.com/v1")]
-    endpoint: String,
+    /// Network to attest on
+    #[arg(long, env = "RESERVE_TRACKER_NETWORK", default_value = NETWORK_NAME)]
+    network: String,
+
+    /// Endpoint (repeatable; the first is used for queries and broadcasts)
+    #[arg(long = "endpoint", env = "RESERVE_TRACKER_ENDPOINTS", value_delimiter = ',', default_value = "https://api.explorer.provable.com/v1")]
+    endpoints: Vec<String>,
+
+    /// Check that each attestation is in the block the endpoint reports, using the block header and a
+    /// Merkle path, and that every endpoint agrees on the block
+    #[arg(long, env = "RESERVE_TRACKER_LIGHT_VERIFICATION")]
+    light_verification: bool,
+
+    /// Number of endpoints that must return the block for the inclusion check to pass (defaults to all of them)
+    #[arg(long, env = "RESERVE_TRACKER_INCLUSION_QUORUM")]
+    inclusion_quorum: Option<usize>,
+
+    /// Priority fee paid on each attestation, in microcredits
+    #[arg(long, env = "RESERVE_TRACKER_PRIORITY_FEE", default_value_t = 0)]
+    priority_fee: u64,
+
+    /// File with the customer balances to commit to as liabilities (optional)
+    #[arg(long, env = "RESERVE_TRACKER_CUSTOMER_BALANCES")]
+    customer_balances: Option<String>,
+
+    /// File to publish the liabilities commitment to
+    #[arg(long, env = "RESERVE_TRACKER_LIABILITIES_FILE", default_value = "liabilities.json")]
+    liabilities_file: String,
+
+    /// File to store the liabilities commitment transactions
+    #[arg(long, env = "RESERVE_TRACKER_LIABILITY_TRANSACTIONS_FILE", default_value = "liability_transactions.json")]
+    liability_transactions_file: String,
+
+    /// What each attestation publishes on-chain; solvency mode still publishes the addresses, so it does not hide the total
+    #[arg(long, value_enum, env = "RESERVE_TRACKER_MODE", default_value_t = Mode::Reserves)]
+    mode: Mode,
+
+    /// Threshold for solvency mode (optional, defaults to the committed liabilities total)
+    #[arg(long, env = "RESERVE_TRACKER_SOLVENCY_THRESHOLD")]
+    solvency_threshold: Option<u64>,
+
+    /// Include bonded and unbonding credits in the reserves, recorded separately from liquid balance
+    #[arg(long, env = "RESERVE_TRACKER_INCLUDE_STAKE")]
+    include_stake: bool,
+
+    /// File with the off-Aleo balance sources, such as Bitcoin and Ethereum nodes, committed together in one attestation (optional, at most 8)
+    #[arg(long, env = "RESERVE_TRACKER_SOURCES_FILE")]
+    sources_file: Option<String>,
+
+    /// View keys to scan for private `credits.aleo` records (repeatable)
+    #[arg(long = "view-key", env = "RESERVE_TRACKER_VIEW_KEYS", value_delimiter = ',', hide_env_values = true)]
+    view_keys: Vec<String>,
+
+    /// File to store the scanned records
+    #[arg(long, env = "RESERVE_TRACKER_RECORDS_FILE", default_value = "records.json")]
+    records_file: String,
+
+    /// Block height to start scanning for records from (optional, defaults to about a day before the first scan)
+    #[arg(long)]
+    record_scan_start: Option<u32>,
+
+    /// URL of an HTTP JSON price feed, with `{asset}`, `{timestamp}` and `{date}` placeholders (optional)
+    #[arg(long, env = "RESERVE_TRACKER_PRICE_URL", conflicts_with = "static_prices")]
+    price_url: Option<String>,
+
+    /// JSON pointer to the price in the price feed response
+    #[arg(long, env = "RESERVE_TRACKER_PRICE_POINTER", default_value = "/price")]
+    price_pointer: String,
+
+    /// File with a static price history per asset, used instead of a price feed (optional)
+    #[arg(long, env = "RESERVE_TRACKER_STATIC_PRICES")]
+    static_prices: Option<String>,
+
+    /// Fiat currency the prices are quoted in
+    #[arg(long, env = "RESERVE_TRACKER_CURRENCY", default_value = "USD")]
+    currency: String,
+
+    /// File to store the price recorded for each attestation
+    #[arg(long, env = "RESERVE_TRACKER_PRICES_FILE", default_value = "prices.json")]
+    prices_file: String,
+
+    /// File to store the webhook deliveries that have not succeeded yet
+    #[arg(long, env = "RESERVE_TRACKER_OUTBOX_FILE", default_value = "outbox.json")]
+    outbox_file: String,
+
+    /// File to store the broadcast transactions that have not been confirmed yet
+    #[arg(long, env = "RESERVE_TRACKER_PENDING_FILE", default_value = "pending.json")]
+    pending_file: String,
+
+    /// File to store the raised alerts and the totals the alert rules compare against
+    #[arg(long, env = "RESERVE_TRACKER_ALERTS_FILE", default_value = "alerts.json")]
+    alerts_file: String,
+
+    /// File to store the signed report of each accepted attestation
+    #[arg(long, env = "RESERVE_TRACKER_REPORTS_FILE", default_value = "reports.json")]
+    reports_file: String,
+
+    /// File to store the outcome of verifying each fetched attestation locally
+    #[arg(long, env = "RESERVE_TRACKER_VERIFICATIONS_FILE", default_value = "verifications.json")]
+    verifications_file: String,
+
+    /// File to store the credits total of each portfolio at each accepted attestation, for the history charts
+    #[arg(long, env = "RESERVE_TRACKER_BREAKDOWNS_FILE", default_value = "breakdowns.json")]
+    breakdowns_file: String,
+
+    /// Directory to store the evidence bundle of each accepted attestation, which the public site serves
+    #[arg(long, env = "RESERVE_TRACKER_EVIDENCE_DIR", default_value = "evidence")]
+    evidence_dir: String,
+
+    /// Seconds since the last successful attestation after which readiness fails (defaults to twice the cadence)
+    #[arg(long, env = "RESERVE_TRACKER_MAX_ATTESTATION_AGE")]
+    max_attestation_age: Option<u64>,
+
+    /// Format of the log lines
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// OpenTelemetry collector to export spans to over OTLP/gRPC (requires the `otlp` feature)
+    #[arg(long)]
+    otlp_endpoint: Option<String>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Check an evidence bundle offline: the proofs, the block hash, the inclusion of the
+    /// transaction, the address hash chain and the mapping entry. Exits non-zero if any check fails.
+    VerifyBundle {
+        /// The bundle, as exported from /evidence/<transaction ID>
+        file: String,
+
+        /// Hash of the block at the bundle's height, as read from a source independent of the
+        /// bundle, e.g. a block explorer or your own node (optional; without it, compare the
+        /// printed hash by hand)
+        #[arg(long)]
+        expected_block_hash: Option<String>,
+    },
+    /// Render the attestation history to a directory of static HTML, JSON and SVG files, from the
+    /// stored reports and verifications, for publishing to object storage or a CDN.
+    ExportSite {
+        /// Directory to write the site to; created if missing
+        #[arg(long, default_value = "site")]
+        out: String,
+
+        /// File the tracked transactions are stored in, to list the attestations without a report
+        #[arg(long, env = "RESERVE_TRACKER_TRANSACTIONS_FILE", default_value = "transactions.json")]
+        transactions_file: String,
+
+        /// File the signed reports are stored in
+        #[arg(long, env = "RESERVE_TRACKER_REPORTS_FILE", default_value = "reports.json")]
+        reports_file: String,
+
+        /// File the verifications are stored in
+        #[arg(long, env = "RESERVE_TRACKER_VERIFICATIONS_FILE", default_value = "verifications.json")]
+        verifications_file: String,
+
+        /// File the per-portfolio totals are stored in
+        #[arg(long, env = "RESERVE_TRACKER_BREAKDOWNS_FILE", default_value = "breakdowns.json")]
+        breakdowns_file: String,
+
+        /// Mode the server attests in; in solvency mode, the per-portfolio totals are left out of the site
+        #[arg(long, value_enum, env = "RESERVE_TRACKER_MODE", default_value_t = Mode::Reserves)]
+        mode: Mode,
+
+        /// Directory the server stores the evidence bundles in; they are copied to the site
+        #[arg(long, env = "RESERVE_TRACKER_EVIDENCE_DIR", default_value = "evidence")]
+        evidence_dir: String,
+
+        /// Endpoint to fetch the evidence bundles missing from both directories from (optional; without
+        /// it, only the bundles already stored or exported are linked)
+        #[arg(long)]
+        endpoint: Option<String>,
+
+        /// Private key of the prover, needed to fetch evidence bundles
+        #[arg(long, env = "RESERVE_TRACKER_PRIVATE_KEY", hide_env_values = true)]
+        private_key: Option<String>,
+    },
+}
+
+/// Runs a subcommand instead of the server.
+fn run_command(command: Command) -> i32 {
+    match command {
+        Command::VerifyBundle { file, expected_block_hash } => {
+            let bundle = match read_json::<EvidenceBundle>(&file) {
+                Ok(bundle) => bundle,
+                Err(e) => {
+                    error!("Failed to read evidence bundle {}: {}", file, e);
+                    return 2;
+                }
+            };
+            println!("Attestation {} in block {}", bundle.transaction_id, bundle.header.height());
+            println!("Block hash {}", bundle.block_hash);
+            let mut checks = verify_bundle(&bundle);
+            // The bundle is only self-consistent; anchoring it to the chain takes a hash from elsewhere.
+            if let Some(expected) = &expected_block_hash {
+                let matches = bundle.block_hash.to_string() == expected.trim();
+                checks.push(("block hash matches the expected hash", if matches {
+                    Ok(())
+                } else {
+                    Err(anyhow!("The bundle's block hash is not {}", expected.trim()))
+                }));
+            }
+            let mut failed = 0;
+            for (check, result) in checks {
+                match result {
+                    Ok(()) => println!("  ok      {}", check),
+                    Err(e) => {
+                        println!("  FAILED  {}: {}", check, e);
+                        failed += 1;
+                    }
+                }
+            }
+            if failed == 0 && expected_block_hash.is_none() {
+                println!(
+                    "The checks only show the bundle is consistent with itself. Before trusting it, compare the block \
+                     hash above with the hash of block {} from a source you trust, or pass it with --expected-block-hash.",
+                    bundle.header.height()
+                );
+            }
+            match failed {
+                0 if expected_block_hash.is_none() => println!("All checks passed, pending the block hash comparison"),
+                0 => println!("All checks passed"),
+                _ => println!("{} checks failed", failed),
+            }
+            (failed > 0) as i32
+        }
+        Command::ExportSite { out, transactions_file, reports_file, verifications_file, breakdowns_file, mode, evidence_dir, endpoint, private_key } => {
+            let reports = match read_json::<HashMap<String, SignedReport>>(&reports_file) {
+                Ok(reports) => reports,
+                Err(e) => {
+                    error!("Failed to read reports {}: {}", reports_file, e);
+                    return 2;
+                }
+            };
+            // Attestations that were never verified are shown as not checked yet.
+            let verifications = read_json(&verifications_file).unwrap_or_default();
+            let breakdowns = match mode {
+                Mode::Reserves => read_json(&breakdowns_file).unwrap_or_default(),
+                Mode::Solvency => HashMap::new(),
+            };
+            let transactions = read_json::<Vec<TrackedTransaction>>(&transactions_file).unwrap_or_default();
+            if let Err(e) = copy_bundles(Path::new(&evidence_dir), &Path::new(&out).join("evidence")) {
+                warn!("Failed to copy the stored evidence bundles: {}", e);
+            }
+            let fetch = endpoint.as_deref().zip(private_key.as_deref());
+            if endpoint.is_some() && fetch.is_none() {
+                warn!("No private key given; only the evidence bundles already exported are linked");
+            }
+            match export_site(Path::new(&out), &transactions, &reports, &verifications, &breakdowns, fetch, NETWORK_NAME) {
+                Ok(()) => 0,
+                Err(e) => {
+                    error!("{}", e);
+                    1
+                }
+            }
+        }
+    }
+}
+
+/// Copies the stored evidence bundles that are not in the site yet.
+fn copy_bundles(from: &Path, to: &Path) -> std::io::Result<()> {
+    if !from.is_dir() {
+        return Ok(());
+    }
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if !target.exists() {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a JSON file written by the server.
+fn read_json<T: serde::de::DeserializeOwned>(file: &str) -> Result<T, String> {
+    std::fs::read_to_string(file)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| serde_json::from_str(&contents).map_err(|e| e.to_string()))
 }
 
 #[tokio::main]
 async fn main() {
-    let cli = Cli::parse();
+    // The subcommands do not take the server's required flags, so they are parsed on their own
+    let matches = Command::augment_subcommands(Cli::command()).subcommand_negates_reqs(true).get_matches();
+    if matches.subcommand().is_some() {
+        let command = Command::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        telemetry::init(LogFormat::Text, None).unwrap_or_else(|e| panic!("Failed to set up logging: {}", e));
+        let code = tokio::task::spawn_blocking(move || run_command(command)).await.unwrap_or(1);
+        std::process::exit(code);
+    }
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    telemetry::init(cli.log_format, cli.otlp_endpoint.clone())
+        .unwrap_or_else(|e| panic!("Failed to set up logging: {}", e));
+
+    // Merge in the config file, if one is provided, and check every setting up front
+    let config = match &cli.config {
+        Some(file) => load_config(file).unwrap_or_else(|e| {
+            error!("{}", e);
+            std::process::exit(2);
+        }),
+        None => FileConfig::default(),
+    };
+    let base_cli = cli.clone();
+    apply_config(&mut cli, &matches, &config);
+    let inputs = validate(&cli, &config).await.unwrap_or_else(|errors| {
+        for e in &errors {
+            error!("Invalid configuration: {}", e);
+        }
+        error!("Found {} configuration errors", errors.len());
+        std::process::exit(2);
+    });
+    let running_cli = cli.clone();
 
     // Load addresses from the specified file
     let addresses = load_addresses(&cli.file).await.unwrap_or_else(|_| Vec::new());
+    // Load the attested assets from the specified file
+    let assets = load_assets(&cli.assets_file).await.unwrap_or_default();
     // Load transactions from the specified file
     let transactions = load_transactions(&cli.transactions_file).await.unwrap_or_default();
+    // Load the liabilities commitment transactions from the specified file
+    let liability_transactions = load_transactions(&cli.liability_transactions_file).await.unwrap_or_default();
+
+    // Load the scanned records, starting a fresh scan at the configured height
+    let mut records = load_records(&cli.records_file).await.unwrap_or_default();
+    if let (0, Some(start)) = (records.scanned_height, cli.record_scan_start) {
+        records = RecordStore { scanned_height: start, ..records };
+    }
+
+    // Set up the price feed, if one is configured; the static prices were read by `validate`
+    let price_feed: Option<Arc<dyn PriceFeed>> = match (cli.price_url, inputs.static_prices) {
+        (Some(url), _) => Some(Arc::new(HttpJsonFeed { url, pointer: cli.price_pointer })),
+        (None, Some(prices)) => Some(Arc::new(StaticFileFeed { prices })),
+        (None, None) => None,
+    };
+    // Load the webhook deliveries left over from the last run
+    let outbox = load_outbox(&cli.outbox_file).await.unwrap_or_default();
+    // Load the transactions that were still waiting for confirmation
+    let pending = load_pending(&cli.pending_file).await.unwrap_or_default();
+    // Load the raised alerts and the totals of the last attestations
+    let alerts = load_alerts(&cli.alerts_file).await.unwrap_or_default();
+    // Load the signed reports of past attestations
+    let reports = load_reports(&cli.reports_file).await.unwrap_or_default();
+    // Load the outcomes of verifying past attestations locally
+    let verifications = load_verifications(&cli.verifications_file).await.unwrap_or_default();
+    // Load the per-portfolio totals of past attestations
+    let breakdowns = load_breakdowns(&cli.breakdowns_file).await.unwrap_or_default();
+    // Load the prices recorded for past attestations
+    let prices = load_prices(&cli.prices_file).await.unwrap_or_default();
+    // Seed the time of the last accepted reserves attestation from the persisted history
+    let last_accepted = reports.values()
+        .filter(|signed| transactions.iter().any(|tracked: &TrackedTransaction| {
+            tracked.id == signed.report.transaction_id && tracked.kind == TransactionKind::Reserves
+        }))
+        .map(|signed| signed.report.timestamp)
+        .chain(alerts.last_accepted)
+        .max();
+    if let Some(last_accepted) = last_accepted {
+        METRICS.mark_accepted(last_accepted);
+    }
+
+    // Publish the commitment of the liabilities tree built from the customer balance file, if one is provided
+    if let Some(tree) = &inputs.liabilities {
+        if let Err(e) = save_liability_commitment(&tree.commitment(), &cli.liabilities_file).await {
+            error!("Failed to publish liabilities commitment: {}", e);
+        }
+    }
 
     // Channel for signaling the background task
     let (tx, rx) = mpsc::channel(10);
 
-    let app_state = AppState {
+    let mut app_state = AppState {
         addresses,
         transactions,
         task_tx: tx.clone(),
         private_key: cli.private_key,
-        endpoint: cli.endpoint,
+        endpoint: cli.endpoints[0].clone(),
+        endpoints: cli.endpoints,
+        light_verification: cli.light_verification,
+        inclusion_quorum: cli.inclusion_quorum,
+        priority_fee: cli.priority_fee,
         transactions_file: cli.transactions_file,
+        liabilities: inputs.liabilities,
+        liability_transactions,
+        liability_transactions_file: cli.liability_transactions_file,
+        mode: cli.mode,
+        solvency_threshold: cli.solvency_threshold,
+        include_stake: cli.include_stake,
+        assets,
+        sources: inputs.sources,
+        source_totals: Vec::new(),
+        view_keys: cli.view_keys,
+        records,
+        records_file: cli.records_file,
+        price_feed,
+        currency: cli.currency,
+        prices,
+        prices_file: cli.prices_file,
+        started: chrono::Utc::now(),
+        last_run: None,
+        last_success: None,
+        max_attestation_age: cli.max_attestation_age.unwrap_or(2 * cli.cadence),
+        webhooks: config.webhooks.clone(),
+        outbox,
+        outbox_file: cli.outbox_file,
+        pending,
+        pending_file: cli.pending_file,
+        alert_rules: config.alerts.clone(),
+        alerts,
+        alerts_file: cli.alerts_file,
+        reports,
+        reports_file: cli.reports_file,
+        verifications,
+        verifications_file: cli.verifications_file,
+        portfolios: config.portfolios.clone(),
+        breakdowns,
+        breakdowns_file: cli.breakdowns_file,
+        evidence: inputs.evidence,
+        evidence_dir: cli.evidence_dir,
+        cadence: cli.cadence,
+        jobs: Vec::new(),
     };
 
+    add_portfolios(&mut app_state, &config.portfolios);
+
     let app_state = Arc::new(RwLock::new(app_state));
 
+    // Reload the config file on SIGHUP
+    #[cfg(unix)]
+    spawn_reload_task(base_cli, matches, running_cli, app_state.clone());
+
     // Spawn background task with access to app_state
     let bg_handle = spawn_background_task(rx, cli.cadence, app_state.clone());
+    // Watch broadcast transactions until they are confirmed, and deliver webhooks
+    spawn_confirmation_task(app_state.clone());
+    spawn_delivery_task(app_state.clone());
 
     // Setup Axum routes
     let app = Router::new()
@@ -86,14 +538,74 @@ async fn main() {
         .with_state(app_state.clone())
         // transactions
         .route("/transactions", get(get_transactions_page).post(handle_transactions_form))
+        .with_state(app_state.clone())
+        // liabilities
+        .route("/liabilities", get(get_liabilities_page).post(handle_liabilities_form))
+        .with_state(app_state.clone())
+        // customer inclusion portal
+        .route("/inclusion", get(get_inclusion_page).post(handle_inclusion_form))
+        .with_state(app_state.clone())
+        // Liveness and readiness
+        .route("/healthz", get(get_healthz))
+        .route("/readyz", get(get_readyz))
+        .with_state(app_state.clone())
+        // Prometheus metrics
+        .route("/metrics", get(get_metrics))
+        .with_state(app_state.clone())
+        // Signed attestation reports
+        .route("/reports/:txid", get(get_report))
+        .with_state(app_state.clone())
+        // Evidence bundles for offline verification
+        .route("/evidence/:txid", get(get_evidence))
+        .with_state(app_state.clone())
+        // Preview of the public transparency page
+        .route("/public", get(get_public_page))
+        .with_state(app_state.clone())
+        // Atom feed of accepted attestations
+        .route("/feed.atom", get(get_atom_feed))
+        .with_state(app_state.clone())
+        // Embeddable status badge and widget
+        .route("/badge.svg", get(get_badge))
+        .route("/widget.json", get(get_widget))
+        .with_state(app_state.clone())
+        // Versioned JSON API
+        .route("/api/v1/addresses", get(api_list_addresses).post(api_add_address))
+        .route("/api/v1/addresses/:address", delete(api_remove_address))
+        .route("/api/v1/attestations", get(api_list_attestations))
+        .route("/api/v1/attestations/:txid", get(api_get_attestation))
+        .route("/api/v1/runs", get(api_list_runs).post(api_trigger_run))
+        .route("/api/v1/runs/:id", get(api_get_run))
+        .route("/api/v1/config", get(api_get_config))
         .with_state(app_state.clone());
 
+    // Serve the read-only transparency site on its own port, if one is configured
+    if let Some(public_port) = cli.public_port {
+        let public = Router::new()
+            .route("/", get(get_public_page))
+            .route("/feed.atom", get(get_atom_feed))
+            .route("/badge.svg", get(get_badge))
+            .route("/widget.json", get(get_widget))
+            .route("/reports/:txid", get(get_report))
+            .route("/evidence/:txid", get(get_public_evidence))
+            .with_state(app_state.clone());
+        let public_addr = format!("0.0.0.0:{}", public_port);
+        info!("Public transparency site running on http://{}", public_addr);
+        tokio::spawn(async move {
+            if let Err(e) = axum::Server::bind(&public_addr.parse().unwrap()).serve(public.into_make_service()).await {
+                error!("Public transparency site stopped: {}", e);
+            }
+        });
+    }
+
     let addr = format!("0.0.0.0:{}", cli.port);
-    println!("Server running on http://{}", addr);
-    println!("Using storage file: {}", cli.file);
-    println!("Background task cadence: {}s", cli.cadence);
-    println!("Using endpoint: {}", app_state.read().await.endpoint);
-    println!("Private key provided: {}", &app_state.read().await.private_key);
+    info!("Server running on http://{}", addr);
+    info!("Using storage file: {}", cli.file);
+    info!("Background task cadence: {}s", cli.cadence);
+    info!("Attestation mode: {:?}", cli.mode);
+    if cli.mode == Mode::Solvency {
+        warn!("Solvency mode publishes the attested addresses, so the reserve total can still be recomputed from their balances");
+    }
+    info!("Using endpoint: {}", app_state.read().await.endpoint);
 
     // Create a shutdown signal future
     let shutdown_signal = async {
@@ -112,13 +624,21 @@ async fn main() {
 
     // Wait for background task to finish
     if let Err(e) = bg_handle.await {
-        eprintln!("Background task ended with error: {:?}", e);
+        error!("Background task ended with error: {:?}", e);
     }
 
     // Save the addresses
     if let Err(e) = save_addresses(&app_state.read().await.addresses, &cli.file).await {
-        eprintln!("Failed to save addresses: {}", e);
+        error!("Failed to save addresses: {}", e);
     } else {
-        println!("Addresses saved. Shutting down.");
+        info!("Addresses saved. Shutting down.");
     };
+
+    // Save the assets
+    if let Err(e) = save_assets(&app_state.read().await.assets, &cli.assets_file).await {
+        error!("Failed to save assets: {}", e);
+    };
+
+    // Flush any spans still waiting to be exported
+    telemetry::shutdown();
 }
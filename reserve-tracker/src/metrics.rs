@@ -0,0 +1,180 @@
+use once_cell::sync::Lazy;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::Registry;
+use snarkvm::prelude::{anyhow, Result};
+use tracing::error;
+use std::sync::atomic::AtomicU64;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct OutcomeLabels {
+    pub outcome: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct FunctionLabels {
+    pub function: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct PathLabels {
+    pub path: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct AssetLabels {
+    pub asset: String,
+}
+
+/// The metrics exposed on `/metrics`.
+pub struct Metrics {
+    registry: Registry,
+    attestations: Family<OutcomeLabels, Counter>,
+    proving_seconds: Family<FunctionLabels, Histogram>,
+    broadcast_seconds: Family<FunctionLabels, Histogram>,
+    rest_seconds: Family<PathLabels, Histogram>,
+    rest_errors: Family<PathLabels, Counter>,
+    last_attestation: Gauge,
+    seconds_since_last_attestation: Gauge,
+    addresses: Gauge,
+    last_total: Family<AssetLabels, Gauge<f64, AtomicU64>>,
+    started: Instant,
+    /// Unix time of the last accepted attestation.
+    last_accepted: std::sync::Mutex<Option<i64>>,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let mut registry = Registry::with_prefix("reserve_tracker");
+        let attestations = Family::<OutcomeLabels, Counter>::default();
+        registry.register("attestations", "Attestation runs, by outcome", attestations.clone());
+        // Proving takes from seconds to minutes, so the buckets run from 1s to about 68 minutes.
+        let proving_seconds = Family::<FunctionLabels, Histogram>::new_with_constructor(|| {
+            Histogram::new(exponential_buckets(1.0, 2.0, 13))
+        });
+        registry.register("proving_seconds", "Time spent executing each function in the VM", proving_seconds.clone());
+        let broadcast_seconds = Family::<FunctionLabels, Histogram>::new_with_constructor(|| {
+            Histogram::new(exponential_buckets(0.05, 2.0, 10))
+        });
+        registry.register("broadcast_seconds", "Time spent broadcasting each transaction", broadcast_seconds.clone());
+        let rest_seconds = Family::<PathLabels, Histogram>::new_with_constructor(|| {
+            Histogram::new(exponential_buckets(0.01, 2.0, 12))
+        });
+        registry.register("rest_request_seconds", "Latency of REST calls to the endpoint, by path", rest_seconds.clone());
+        let rest_errors = Family::<PathLabels, Counter>::default();
+        registry.register("rest_request_errors", "Failed REST calls to the endpoint, by path", rest_errors.clone());
+        let last_attestation = Gauge::default();
+        registry.register(
+            "last_attestation_timestamp_seconds",
+            "Unix time of the last accepted attestation, or 0 if none was ever accepted",
+            last_attestation.clone(),
+        );
+        let seconds_since_last_attestation = Gauge::default();
+        registry.register(
+            "seconds_since_last_attestation",
+            "Seconds since the last accepted attestation, or since startup if none was ever accepted",
+            seconds_since_last_attestation.clone(),
+        );
+        let addresses = Gauge::default();
+        registry.register("tracked_addresses", "Number of tracked addresses", addresses.clone());
+        let last_total = Family::<AssetLabels, Gauge<f64, AtomicU64>>::default();
+        registry.register(
+            "last_attested_total",
+            "Total recorded by the last accepted attestation, in the smallest unit of each asset",
+            last_total.clone(),
+        );
+
+        Self {
+            registry,
+            attestations,
+            proving_seconds,
+            broadcast_seconds,
+            rest_seconds,
+            rest_errors,
+            last_attestation,
+            seconds_since_last_attestation,
+            addresses,
+            last_total,
+            started: Instant::now(),
+            last_accepted: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Counts an attestation run by outcome.
+    pub fn record_attestation(&self, success: bool) {
+        let outcome = if success { "success" } else { "failure" };
+        self.attestations.get_or_create(&OutcomeLabels { outcome: outcome.to_string() }).inc();
+    }
+
+    /// Marks the unix time an attestation was accepted, on confirmation or from the persisted
+    /// history at startup. An earlier time than the last one is ignored.
+    pub fn mark_accepted(&self, timestamp: i64) {
+        let mut last_accepted = self.last_accepted.lock().unwrap();
+        if last_accepted.is_none_or(|last| last < timestamp) {
+            *last_accepted = Some(timestamp);
+            self.last_attestation.set(timestamp);
+        }
+    }
+
+    pub fn observe_proving(&self, function: &str, elapsed: Duration) {
+        self.proving_seconds.get_or_create(&FunctionLabels { function: function.to_string() }).observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_broadcast(&self, function: &str, elapsed: Duration) {
+        self.broadcast_seconds.get_or_create(&FunctionLabels { function: function.to_string() }).observe(elapsed.as_secs_f64());
+    }
+
+    /// Records the latency of a REST call, and counts it as an error if it failed.
+    pub fn observe_request(&self, path: &str, elapsed: Duration, success: bool) {
+        let labels = PathLabels { path: path.to_string() };
+        self.rest_seconds.get_or_create(&labels).observe(elapsed.as_secs_f64());
+        if !success {
+            self.rest_errors.get_or_create(&labels).inc();
+        }
+    }
+
+    pub fn set_last_total(&self, asset: &str, total: u128) {
+        self.last_total.get_or_create(&AssetLabels { asset: asset.to_string() }).set(total as f64);
+    }
+
+    /// Encodes the metrics in the Prometheus text format.
+    pub fn encode(&self, addresses: usize) -> String {
+        self.addresses.set(addresses as i64);
+        let since = match *self.last_accepted.lock().unwrap() {
+            Some(last) => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                (now.as_secs() as i64 - last).max(0)
+            }
+            None => self.started.elapsed().as_secs() as i64,
+        };
+        self.seconds_since_last_attestation.set(since);
+
+        let mut body = String::new();
+        if let Err(e) = prometheus_client::encoding::text::encode(&mut body, &self.registry) {
+            error!("Failed to encode metrics: {}", e);
+        }
+        body
+    }
+}
+
+/// Times a GET request to the endpoint under the given path, e.g. `/find/blockHash`.
+pub fn timed_call(path: &str, request: ureq::Request) -> Result<ureq::Response> {
+    let start = Instant::now();
+    let response = request.call();
+    METRICS.observe_request(path, start.elapsed(), response.is_ok());
+    response.map_err(|e| anyhow!("{}", e))
+}
+
+/// Times a JSON POST request to the endpoint under the given path.
+pub fn timed_send_json(path: &str, request: ureq::Request, body: serde_json::Value) -> Result<ureq::Response> {
+    let start = Instant::now();
+    let response = request.send_json(body);
+    METRICS.observe_request(path, start.elapsed(), response.is_ok());
+    response.map_err(|e| anyhow!("{}", e))
+}
@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::utilities::{AttestationResult, HTTP_AGENT};
+
+/// Prices are kept in millionths of the currency, so sub-cent prices keep their precision.
+pub const PRICE_DECIMALS: u32 = 6;
+/// Fiat values are in minor units of the currency, e.g. cents.
+pub const CURRENCY_DECIMALS: u32 = 2;
+
+/// A source of fiat prices for the attested assets.
+pub trait PriceFeed: Send + Sync {
+    /// The price of one whole unit of the asset, e.g. one credit, at the unix timestamp, in
+    /// millionths of the currency.
+    fn price_at(&self, asset: &str, timestamp: i64) -> Result<u64, String>;
+}
+
+/// Reads prices from an HTTP endpoint returning JSON.
+///
+/// The URL may contain `{asset}`, `{timestamp}` and `{date}` (as `dd-mm-yyyy`) placeholders,
+/// and the price is read from the response at the JSON pointer, e.g. `/market_data/current_price/usd`.
+pub struct HttpJsonFeed {
+    pub url: String,
+    pub pointer: String,
+}
+
+impl PriceFeed for HttpJsonFeed {
+    fn price_at(&self, asset: &str, timestamp: i64) -> Result<u64, String> {
+        let date = chrono::DateTime::from_timestamp(timestamp, 0)
+            .ok_or_else(|| format!("Invalid timestamp {}", timestamp))?
+            .format("%d-%m-%Y")
+            .to_string();
+        let url = self.url
+            .replace("{asset}", asset)
+            .replace("{timestamp}", &timestamp.to_string())
+            .replace("{date}", &date);
+        let response = HTTP_AGENT.get(&url)
+            .call()
+            .map_err(|e| format!("Failed to get price: {}", e))?
+            .into_json::<serde_json::Value>()
+            .map_err(|e| format!("Failed to parse price: {}", e))?;
+        // Some feeds quote prices as strings to keep their precision.
+        match response.pointer(&self.pointer) {
+            Some(serde_json::Value::Number(price)) => parse_price(&price.to_string()),
+            Some(serde_json::Value::String(price)) => parse_price(price),
+            _ => None,
+        }
+        .ok_or_else(|| format!("No {} price at {} in the response", asset, self.pointer))
+    }
+}
+
+/// A price at a point in time, as listed in a static price file.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PricePoint {
+    pub timestamp: i64,
+    pub price: f64,
+}
+
+/// Reads prices from a file mapping each asset to its price history.
+pub struct StaticFileFeed {
+    pub prices: HashMap<String, Vec<PricePoint>>,
+}
+
+impl PriceFeed for StaticFileFeed {
+    fn price_at(&self, asset: &str, timestamp: i64) -> Result<u64, String> {
+        // Use the latest price listed at or before the timestamp.
+        self.prices
+            .get(asset)
+            .and_then(|points| {
+                points.iter()
+                    .filter(|point| point.timestamp <= timestamp)
+                    .max_by_key(|point| point.timestamp)
+            })
+            .and_then(|point| parse_price(&point.price.to_string()))
+            .ok_or_else(|| format!("No {} price listed at or before {}", asset, timestamp))
+    }
+}
+
+/// Parses a decimal price, such as `0.25`, into millionths of the currency. Digits past the
+/// sixth decimal are dropped.
+pub fn parse_price(price: &str) -> Option<u64> {
+    let price = price.trim();
+    let (whole, fraction) = price.split_once('.').unwrap_or((price, ""));
+    let exact = (!whole.is_empty() && whole.chars().all(|c| c.is_ascii_digit()) && fraction.chars().all(|c| c.is_ascii_digit()))
+        .then(|| {
+            let fraction = format!("{:0<width$}", &fraction[..fraction.len().min(PRICE_DECIMALS as usize)], width = PRICE_DECIMALS as usize);
+            whole.parse::<u64>().ok()?.checked_mul(10u64.pow(PRICE_DECIMALS))?.checked_add(fraction.parse().ok()?)
+        })
+        .flatten();
+    // Feeds may write very small or large prices with an exponent, e.g. `1e-7`.
+    exact.or_else(|| {
+        price.parse::<f64>().ok()
+            .filter(|price| price.is_finite() && *price >= 0.0)
+            .map(|price| (price * 10f64.powi(PRICE_DECIMALS as i32)).round() as u64)
+    })
+}
+
+/// The price recorded for an asset of an attestation, at its block timestamp.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(from = "StoredPrice")]
+pub struct RecordedPrice {
+    pub asset: String,
+    pub timestamp: i64,
+    /// The price of one whole unit of the asset, in millionths of the currency.
+    pub price_micros: u64,
+    pub currency: String,
+}
+
+/// Earlier versions stored the price as a float, in whole units of the currency.
+#[derive(Deserialize)]
+struct StoredPrice {
+    asset: String,
+    timestamp: i64,
+    #[serde(default)]
+    price_micros: Option<u64>,
+    #[serde(default)]
+    price: Option<f64>,
+    currency: String,
+}
+
+impl From<StoredPrice> for RecordedPrice {
+    fn from(stored: StoredPrice) -> Self {
+        let price_micros = stored.price_micros
+            .or_else(|| parse_price(&stored.price?.to_string()))
+            .unwrap_or_default();
+        Self { asset: stored.asset, timestamp: stored.timestamp, price_micros, currency: stored.currency }
+    }
+}
+
+/// The prices recorded for an attestation, one per valued asset. Earlier versions stored a
+/// single price.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum StoredPrices {
+    One(RecordedPrice),
+    Many(Vec<RecordedPrice>),
+}
+
+impl From<StoredPrices> for Vec<RecordedPrice> {
+    fn from(stored: StoredPrices) -> Self {
+        match stored {
+            StoredPrices::One(price) => vec![price],
+            StoredPrices::Many(prices) => prices,
+        }
+    }
+}
+
+/// The assets of an attestation result that can be valued, with their totals in the smallest
+/// unit and the decimals of that unit. Token totals are not valued, since the registry decimals
+/// are not known here.
+pub fn valued_totals(result: &AttestationResult) -> Vec<(String, u128, u32)> {
+    result.totals()
+        .into_iter()
+        .filter_map(|(asset, total)| {
+            let (asset, decimals) = match asset.as_str() {
+                "credits" | "private credits" => ("credits", 6),
+                "BTC" => ("BTC", 8),
+                "ETH" => ("ETH", 18),
+                _ => return None,
+            };
+            Some((asset.to_string(), total, decimals))
+        })
+        .collect()
+}
+
+/// The value of a total in the smallest unit of an asset, at a price in millionths of the
+/// currency, in minor units of the currency. Fractions of a minor unit are dropped.
+pub fn fiat_value(total: u128, decimals: u32, price_micros: u64) -> Option<u128> {
+    let scale = 10u128.checked_pow(decimals + PRICE_DECIMALS - CURRENCY_DECIMALS)?;
+    Some(total.checked_mul(price_micros as u128)? / scale)
+}
+
+/// Formats an amount in minor units of the currency, e.g. `12345` as `123.45`.
+pub fn format_minor(amount: u128) -> String {
+    let scale = 10u128.pow(CURRENCY_DECIMALS);
+    format!("{}.{:0width$}", amount / scale, amount % scale, width = CURRENCY_DECIMALS as usize)
+}
+
+/// Formats a price in millionths of the currency, e.g. `250000` as `0.250000`.
+pub fn format_price(price_micros: u64) -> String {
+    let scale = 10u64.pow(PRICE_DECIMALS);
+    format!("{}.{:0width$}", price_micros / scale, price_micros % scale, width = PRICE_DECIMALS as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_prices_exactly() {
+        assert_eq!(parse_price("0.25"), Some(250_000));
+        assert_eq!(parse_price("1234"), Some(1_234_000_000));
+        assert_eq!(parse_price("0.1234567"), Some(123_456));
+        assert_eq!(parse_price("1e-6"), Some(1));
+        assert_eq!(parse_price("-1"), None);
+        assert_eq!(parse_price("abc"), None);
+    }
+
+    #[test]
+    fn values_totals_in_minor_units() {
+        // 12.5 credits at 0.25 is 3.12, dropping the fraction of a cent.
+        assert_eq!(fiat_value(12_500_000, 6, 250_000), Some(312));
+        // 1 ETH at 2000.5 is 2000.50.
+        assert_eq!(fiat_value(10u128.pow(18), 18, 2_000_500_000), Some(200_050));
+        assert_eq!(fiat_value(u128::MAX, 6, 2), None);
+        assert_eq!(format_minor(200_050), "2000.50");
+        assert_eq!(format_minor(7), "0.07");
+        assert_eq!(format_price(250_000), "0.250000");
+    }
+
+    #[test]
+    fn loads_legacy_float_prices() {
+        let stored: StoredPrices = serde_json::from_str(r#"{"asset":"credits","timestamp":1,"price":0.25,"currency":"USD"}"#).unwrap();
+        let prices: Vec<RecordedPrice> = stored.into();
+        assert_eq!(prices.len(), 1);
+        assert_eq!(prices[0].price_micros, 250_000);
+    }
+}
@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use snarkvm::prelude::{Address, Entry, Field, GraphKey, Identifier, Input, Literal, Output, Plaintext, PrivateKey, Record, ViewKey};
+use std::str::FromStr;
+use tracing::warn;
+
+use crate::CurrentNetwork;
+use crate::utilities::{find_transition_id, get_blocks, get_latest_height};
+
+/// The maximum number of blocks the endpoint returns per request.
+const BLOCKS_PER_REQUEST: u32 = 50;
+/// How far back a first scan starts without a configured height, about a day of blocks.
+/// Scanning from genesis would take days of requests.
+const DEFAULT_SCAN_LOOKBACK: u32 = 30_000;
+
+/// A `credits.aleo` record owned by one of the configured view keys.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ScannedRecord {
+    pub commitment: String,
+    pub tag: String,
+    pub owner: String,
+    pub microcredits: u64,
+    pub height: u32,
+    /// The decrypted record, needed to spend it in a proof.
+    pub record: String,
+    pub spent: bool,
+}
+
+/// The records found so far, and how far the chain has been scanned.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct RecordStore {
+    pub scanned_height: u32,
+    pub records: Vec<ScannedRecord>,
+}
+
+impl RecordStore {
+    /// The total microcredits held in unspent records owned by `owner`. Only the prover's own
+    /// records can be spent in a proof, so these are the ones that get attested.
+    pub fn unspent_total(&self, owner: &str) -> u64 {
+        self.records.iter().filter(|r| !r.spent && r.owner == owner).map(|r| r.microcredits).sum()
+    }
+
+    /// The total microcredits held in unspent records of the other view keys, which are scanned
+    /// but cannot be attested.
+    pub fn unattested_total(&self, owner: &str) -> u64 {
+        self.records.iter().filter(|r| !r.spent && r.owner != owner).map(|r| r.microcredits).sum()
+    }
+}
+
+/// Scans the blocks since the last scan for `credits.aleo` records owned by the view keys,
+/// and marks the known records whose tags show up as transition inputs as spent.
+pub fn scan_records(store: &mut RecordStore, view_keys: &[String], endpoint: &str, network: &str) -> Result<(), String> {
+    let keys = view_keys.iter()
+        .map(|view_key| {
+            let view_key = ViewKey::<CurrentNetwork>::from_str(view_key)
+                .map_err(|e| format!("Failed to parse view key: {}", e))?;
+            let graph_key = GraphKey::try_from(&view_key)
+                .map_err(|e| format!("Failed to derive graph key: {}", e))?;
+            let owner = Address::try_from(&view_key)
+                .map_err(|e| format!("Failed to derive address: {}", e))?;
+            Ok((view_key, graph_key, owner))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let latest = get_latest_height(endpoint, network)
+        .map_err(|e| format!("Failed to scan records: {}", e))?;
+    if store.scanned_height == 0 {
+        store.scanned_height = latest.saturating_sub(DEFAULT_SCAN_LOOKBACK);
+        warn!("No record scan start height set, scanning from height {}; older records are not found", store.scanned_height);
+    }
+
+    while store.scanned_height <= latest {
+        let end = (store.scanned_height + BLOCKS_PER_REQUEST).min(latest + 1);
+        let blocks = get_blocks::<CurrentNetwork>(store.scanned_height, end, endpoint, network)
+            .map_err(|e| format!("Failed to scan records: {}", e))?;
+
+        for block in &blocks {
+            for transition in block.transitions() {
+                // Mark the known records consumed by this transition as spent.
+                for input in transition.inputs() {
+                    if let Input::Record(_, tag) = input {
+                        let tag = tag.to_string();
+                        store.records.iter_mut().filter(|r| r.tag == tag).for_each(|r| r.spent = true);
+                    }
+                }
+                if transition.program_id().to_string() != "credits.aleo" {
+                    continue;
+                }
+                // Collect the new records owned by any of the view keys.
+                for output in transition.outputs() {
+                    let Output::Record(commitment, _, Some(ciphertext)) = output else { continue };
+                    for (view_key, graph_key, owner) in &keys {
+                        if !ciphertext.is_owner(view_key) {
+                            continue;
+                        }
+                        let record = ciphertext.decrypt(view_key)
+                            .map_err(|e| format!("Failed to decrypt record: {}", e))?;
+                        // Skip empty records, such as the change left behind by a proof.
+                        let amount = microcredits(&record)?;
+                        if amount == 0 {
+                            continue;
+                        }
+                        let tag = Record::<CurrentNetwork, Plaintext<CurrentNetwork>>::tag(graph_key.sk_tag(), *commitment)
+                            .map_err(|e| format!("Failed to compute record tag: {}", e))?;
+                        store.records.push(ScannedRecord {
+                            commitment: commitment.to_string(),
+                            tag: tag.to_string(),
+                            owner: owner.to_string(),
+                            microcredits: amount,
+                            height: block.height(),
+                            record: record.to_string(),
+                            spent: false,
+                        });
+                    }
+                }
+            }
+        }
+
+        store.scanned_height = end;
+    }
+
+    Ok(())
+}
+
+/// Returns the unspent records owned by the private key, after checking each serial number
+/// against the endpoint, so a record spent since the last scan is never put into a proof.
+pub fn fresh_records(
+    store: &mut RecordStore,
+    private_key: &PrivateKey<CurrentNetwork>,
+    endpoint: &str,
+    network: &str,
+) -> Result<Vec<Record<CurrentNetwork, Plaintext<CurrentNetwork>>>, String> {
+    let owner = Address::try_from(private_key)
+        .map_err(|e| format!("Failed to derive address: {}", e))?
+        .to_string();
+
+    let others = store.records.iter().filter(|r| !r.spent && r.owner != owner).count();
+    if others > 0 {
+        warn!("Leaving out {} unspent records of other view keys, which only their owner can prove", others);
+    }
+
+    let mut fresh = Vec::new();
+    for scanned in store.records.iter_mut().filter(|r| !r.spent && r.owner == owner) {
+        let commitment = Field::<CurrentNetwork>::from_str(&scanned.commitment)
+            .map_err(|e| format!("Failed to parse commitment: {}", e))?;
+        let serial_number = Record::<CurrentNetwork, Plaintext<CurrentNetwork>>::serial_number(*private_key, commitment)
+            .map_err(|e| format!("Failed to compute serial number: {}", e))?;
+        if find_transition_id(&serial_number.to_string(), endpoint, network)
+            .map_err(|e| format!("Failed to check serial number: {}", e))?
+            .is_some()
+        {
+            scanned.spent = true;
+            continue;
+        }
+        fresh.push(Record::from_str(&scanned.record).map_err(|e| format!("Failed to parse record: {}", e))?);
+    }
+
+    Ok(fresh)
+}
+
+/// Reads the `microcredits` entry of a `credits.aleo/credits` record.
+pub fn microcredits(record: &Record<CurrentNetwork, Plaintext<CurrentNetwork>>) -> Result<u64, String> {
+    let identifier = Identifier::from_str("microcredits").map_err(|e| e.to_string())?;
+    match record.data().get(&identifier) {
+        Some(Entry::Private(Plaintext::Literal(Literal::U64(microcredits), _)))
+        | Some(Entry::Public(Plaintext::Literal(Literal::U64(microcredits), _))) => Ok(**microcredits),
+        _ => Err("Record has no microcredits entry".to_string()),
+    }
+}
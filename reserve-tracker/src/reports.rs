@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use snarkvm::prelude::{anyhow, ensure, Address, PrivateKey, Result, Signature};
+use std::str::FromStr;
+
+use crate::utilities::{AttestationDetails, AttestationResult};
+use crate::CurrentNetwork;
+
+/// A self-describing record of an accepted attestation. The fields are declared in alphabetical
+/// order, so the compact JSON serialization is canonical and is what the signature covers.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AttestationReport {
+    /// The addresses whose balances the attestation covers.
+    pub addresses: Vec<String>,
+    pub asset: String,
+    pub block_height: u32,
+    pub data_hash: String,
+    pub function: String,
+    pub network: String,
+    pub program_id: String,
+    /// Whether the reserves covered the threshold, in solvency mode.
+    pub solvent: Option<bool>,
+    /// The solvency threshold, in solvency mode.
+    pub threshold: Option<String>,
+    /// Unix time of the block.
+    pub timestamp: i64,
+    /// The attested total in the smallest unit of the asset; hidden in solvency mode.
+    pub total: Option<String>,
+    /// The attested total of each asset, when the attestation covers several. The `asset` then
+    /// lists them and `total` is empty.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub totals: BTreeMap<String, String>,
+    pub transaction_id: String,
+    pub version: u32,
+}
+
+/// A report with the prover's signature over its canonical JSON.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SignedReport {
+    pub report: AttestationReport,
+    /// The address of the prover, which signed the report.
+    pub signer: String,
+    pub signature: String,
+}
+
+impl AttestationReport {
+    /// Version 2 added `totals`, which version 1 reports never have.
+    pub const VERSION: u32 = 2;
+
+    /// Builds the report for an accepted attestation from its resolved details.
+    pub fn new(
+        transaction_id: &str,
+        function: &str,
+        addresses: Vec<String>,
+        details: &AttestationDetails,
+        network: &str,
+    ) -> Result<Self> {
+        let (program_id, function) = function.split_once('/')
+            .ok_or_else(|| anyhow!("Invalid function name '{}'", function))?;
+        let totals = details.result.totals();
+        let (threshold, solvent) = match &details.result {
+            AttestationResult::Solvency { threshold, solvent } => (Some(threshold.clone()), Some(*solvent)),
+            _ => (None, None),
+        };
+        Ok(Self {
+            addresses,
+            asset: details.result.asset(),
+            block_height: details.height.parse()?,
+            data_hash: details.data_hash.clone(),
+            function: function.to_string(),
+            network: network.to_string(),
+            program_id: program_id.to_string(),
+            solvent,
+            timestamp: details.unix_timestamp,
+            threshold,
+            total: details.result.total().map(|total| total.to_string()),
+            totals: match totals.len() {
+                0 | 1 => BTreeMap::new(),
+                _ => totals.into_iter().map(|(asset, total)| (asset, total.to_string())).collect(),
+            },
+            transaction_id: transaction_id.to_string(),
+            version: Self::VERSION,
+        })
+    }
+
+    /// The attested total of each asset the report covers.
+    pub fn asset_totals(&self) -> Vec<(String, u128)> {
+        match (&self.total, self.totals.is_empty()) {
+            (Some(total), _) => total.parse().map(|total| vec![(self.asset.clone(), total)]).unwrap_or_default(),
+            (None, false) => self.totals.iter()
+                .filter_map(|(asset, total)| Some((asset.clone(), total.parse().ok()?)))
+                .collect(),
+            (None, true) => Vec::new(),
+        }
+    }
+
+    /// The canonical JSON of the report: fields in alphabetical order, with no whitespace.
+    pub fn canonical_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Signs the canonical JSON with the prover's key.
+    pub fn sign(self, private_key: &str) -> Result<SignedReport> {
+        let private_key = PrivateKey::<CurrentNetwork>::from_str(private_key)?;
+        let signature = private_key.sign_bytes(self.canonical_json()?.as_bytes(), &mut rand::rngs::OsRng)?;
+        Ok(SignedReport {
+            signer: Address::try_from(&private_key)?.to_string(),
+            signature: signature.to_string(),
+            report: self,
+        })
+    }
+}
+
+impl SignedReport {
+    /// Checks that the signer signed the canonical JSON of the report.
+    pub fn verify(&self) -> Result<()> {
+        let signer = Address::<CurrentNetwork>::from_str(&self.signer)?;
+        let signature = Signature::<CurrentNetwork>::from_str(&self.signature)?;
+        ensure!(
+            signature.verify_bytes(&signer, self.report.canonical_json()?.as_bytes()),
+            "The signature does not match the report and signer"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRIVATE_KEY: &str = "APrivateKey1zkp8CZNn3yeCseEtxuVPbDCwSyhGW6yZKUYKfgXmcpoGPWH";
+
+    fn report() -> AttestationReport {
+        AttestationReport {
+            addresses: vec!["aleo1rhgdu77hgyqd3xjj8ucu3jj9r2krwz6mnzyd80gncr5fxcwlh5rsvzp9px".to_string()],
+            asset: "credits".to_string(),
+            block_height: 42,
+            data_hash: "1field".to_string(),
+            function: "prove_public_balance_1".to_string(),
+            network: "testnet".to_string(),
+            program_id: "proof_of_reserves_v0_2_0.aleo".to_string(),
+            solvent: None,
+            threshold: None,
+            timestamp: 1_700_000_000,
+            total: Some("1000000".to_string()),
+            totals: BTreeMap::new(),
+            transaction_id: "at1example".to_string(),
+            version: AttestationReport::VERSION,
+        }
+    }
+
+    #[test]
+    fn signed_report_verifies() {
+        let signed = report().sign(PRIVATE_KEY).unwrap();
+        signed.verify().unwrap();
+        // The signature survives storage.
+        let stored: SignedReport = serde_json::from_str(&serde_json::to_string(&signed).unwrap()).unwrap();
+        stored.verify().unwrap();
+    }
+
+    #[test]
+    fn canonical_json_sorts_the_fields() {
+        let mut report = report();
+        report.totals = BTreeMap::from([("BTC".to_string(), "1".to_string()), ("ETH".to_string(), "2".to_string())]);
+        let canonical = report.canonical_json().unwrap();
+        // snarkVM turns on serde_json's `preserve_order`, so a parsed `Value` keeps the order it was
+        // given in. Compare the keys with a sorted copy, and the JSON with a `BTreeMap` round trip.
+        let parsed = serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&canonical).unwrap();
+        let keys = parsed.keys().cloned().collect::<Vec<_>>();
+        let mut sorted_keys = keys.clone();
+        sorted_keys.sort();
+        assert_eq!(keys, sorted_keys, "the report fields are not declared in alphabetical order");
+        let sorted = serde_json::to_string(&serde_json::from_str::<BTreeMap<String, serde_json::Value>>(&canonical).unwrap()).unwrap();
+        assert_eq!(canonical, sorted, "the canonical JSON is not compact and sorted");
+    }
+
+    #[test]
+    fn tampered_report_does_not_verify() {
+        let mut signed = report().sign(PRIVATE_KEY).unwrap();
+        signed.report.total = Some("2000000".to_string());
+        assert!(signed.verify().is_err());
+
+        let mut signed = report().sign(PRIVATE_KEY).unwrap();
+        signed.signer = "aleo1s3ws5tra87fjycnjrwsjcrnw2qxr8jfqqdugnf0xzqqw29q9m5pqem2u4t".to_string();
+        assert!(signed.verify().is_err());
+    }
+}
@@ -2,12 +2,18 @@ use axum::{response::Html, extract::{Form, State}};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use html_escape::encode_text;
+use snarkvm::prelude::{Address, PrivateKey};
+use std::str::FromStr;
 use crate::state::AppState;
+use crate::CurrentNetwork;
+use crate::alerts::AlertState;
+use crate::webhooks::{emit, WebhookEvent};
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
     address: Option<String>,
-    action: String, // "add" or "remove" or "run_task"
+    token_id: Option<String>,
+    action: String, // "add" or "remove" or "add_asset" or "remove_asset" or "run_task"
 }
 
 pub async fn get_form(State(state): State<Arc<RwLock<AppState>>>) -> Html<String> {
@@ -33,6 +39,61 @@ pub async fn get_form(State(state): State<Arc<RwLock<AppState>>>) -> Html<String
             .join("\n")
     };
 
+    let asset_list = std::iter::once("<li>credits.aleo (always attested)</li>".to_string())
+        .chain(st.assets.iter().map(|token_id| {
+            let safe_token_id = encode_text(token_id);
+            format!(
+                r#"<li>Token {safe_token_id}
+                        <form action="/form" method="post" style="display:inline;">
+                            <input type="hidden" name="token_id" value="{safe_token_id}">
+                            <button type="submit" name="action" value="remove_asset">Remove</button>
+                        </form>
+                       </li>"#
+            )
+        }))
+        .chain(st.portfolios.iter().flat_map(|portfolio| portfolio.assets.iter().map(|token_id| format!(
+            "<li>Token {} (portfolio {}, over its {} addresses)</li>",
+            encode_text(token_id), encode_text(&portfolio.name), portfolio.addresses.len()
+        ))))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let sources_html = if st.source_totals.is_empty() {
+        "<li>No balances read yet.</li>".to_string()
+    } else {
+        st.source_totals
+            .iter()
+            .map(|source| match &source.total {
+                Ok(total) => format!("<li>{}: {} across {} addresses</li>", encode_text(&source.asset), total, source.addresses),
+                Err(e) => format!("<li>{}: failed to read ({})</li>", encode_text(&source.asset), encode_text(e)),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let records_html = if st.view_keys.is_empty() {
+        "<p>No view keys configured.</p>".to_string()
+    } else {
+        let prover = PrivateKey::<CurrentNetwork>::from_str(&st.private_key)
+            .and_then(Address::try_from)
+            .map(|address| address.to_string())
+            .unwrap_or_default();
+        let unattested = st.records.unattested_total(&prover);
+        // Records of other view keys are scanned, but only the prover can spend its own in a proof.
+        let unattested_html = if unattested > 0 {
+            format!("\n                <p><b>Unspent balance of other view keys (not attested):</b> {}</p>", unattested)
+        } else {
+            String::new()
+        };
+        format!(
+            "<p><b>Scanned up to height:</b> {}</p>\n                <p><b>Unspent records:</b> {}</p>\n                <p><b>Unspent balance (attested):</b> {}</p>{}",
+            st.records.scanned_height,
+            st.records.records.iter().filter(|r| !r.spent).count(),
+            st.records.unspent_total(&prover),
+            unattested_html
+        )
+    };
+
     Html(format!(
         r#"
         <html>
@@ -40,6 +101,7 @@ pub async fn get_form(State(state): State<Arc<RwLock<AppState>>>) -> Html<String
                 <title>Address Tracker</title>
             </head>
             <body>
+                {}
                 <h3>Tracked Addresses</h3>
                 <ul>
                     {}
@@ -49,14 +111,53 @@ pub async fn get_form(State(state): State<Arc<RwLock<AppState>>>) -> Html<String
                     <button type="submit" name="action" value="add">Add</button>
                     <button type="submit" name="action" value="run_task">Run Task Now</button>
                 </form>
+                <h3>Attested Assets</h3>
+                <ul>
+                    {}
+                </ul>
+                <form action="/form" method="post">
+                    <input type="text" name="token_id" style="width:400px;" placeholder="Enter token ID (e.g. 123field)">
+                    <button type="submit" name="action" value="add_asset">Add Asset</button>
+                </form>
+                <h3>Balance Sources (Last Run)</h3>
+                <ul>
+                    {}
+                </ul>
+                <h3>Private Records</h3>
+                {}
                 <p><a href="/transactions">View Verification History</a></p>
+                <p><a href="/liabilities">View Liabilities Commitment</a></p>
             </body>
         </html>
         "#,
-        address_list
+        alert_banner(&st.alerts), address_list, asset_list, sources_html, records_html
     ))
 }
 
+/// A banner listing the raised alerts, or nothing if there are none.
+pub fn alert_banner(alerts: &AlertState) -> String {
+    if alerts.active.is_empty() {
+        return String::new();
+    }
+    let items = alerts.active
+        .iter()
+        .map(|alert| {
+            let raised = chrono::DateTime::from_timestamp(alert.raised, 0).map(|time| time.to_string()).unwrap_or_default();
+            format!("<li>{} (since {})</li>", encode_text(&alert.message), raised)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        r#"<div style="background:#fdd;border:1px solid #c00;padding:10px;margin-bottom:20px;">
+                    <b>Alerts</b>
+                    <ul>
+                        {}
+                    </ul>
+                </div>"#,
+        items
+    )
+}
+
 pub async fn handle_form(
     State(state): State<Arc<RwLock<AppState>>>,
     Form(input): Form<FormData>,
@@ -68,12 +169,31 @@ pub async fn handle_form(
                 let trimmed = addr.trim();
                 if !trimmed.is_empty() && !st.addresses.contains(&trimmed.to_string()) {
                     st.addresses.push(trimmed.to_string());
+                    let tracked = st.addresses.len();
+                    emit(&mut st, WebhookEvent::AddressSetChanged { added: vec![trimmed.to_string()], removed: Vec::new(), tracked }).await;
                 }
             }
         }
         "remove" => {
             if let Some(addr) = input.address {
-                st.addresses.retain(|a| a != &addr);
+                if st.addresses.contains(&addr) {
+                    st.addresses.retain(|a| a != &addr);
+                    let tracked = st.addresses.len();
+                    emit(&mut st, WebhookEvent::AddressSetChanged { added: Vec::new(), removed: vec![addr], tracked }).await;
+                }
+            }
+        }
+        "add_asset" => {
+            if let Some(token_id) = input.token_id {
+                let trimmed = token_id.trim();
+                if !trimmed.is_empty() && !st.assets.contains(&trimmed.to_string()) {
+                    st.assets.push(trimmed.to_string());
+                }
+            }
+        }
+        "remove_asset" => {
+            if let Some(token_id) = input.token_id {
+                st.assets.retain(|a| a != &token_id);
             }
         }
         "run_task" => {
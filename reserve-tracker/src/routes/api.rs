@@ -0,0 +1,220 @@
+use axum::{
+    extract::{rejection::JsonRejection, Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use snarkvm::prelude::{Address, PrivateKey};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use crate::background::BackgroundTaskMsg;
+use crate::state::{AppState, Job, JobStatus, Resolution, TrackedTransaction};
+use crate::utilities::get_attestation_details;
+use crate::webhooks::{emit, WebhookEvent};
+use crate::routes::result_label;
+use crate::{CurrentNetwork, NETWORK_NAME};
+
+/// How many finished jobs are kept for their status to be read.
+const MAX_JOBS: usize = 100;
+
+type ApiError = (StatusCode, Json<Value>);
+
+fn api_error(status: StatusCode, message: impl std::fmt::Display) -> ApiError {
+    (status, Json(json!({ "error": message.to_string() })))
+}
+
+/// Turns a malformed request body into a JSON error, like every other API error.
+fn body<T>(payload: Result<Json<T>, JsonRejection>) -> Result<T, ApiError> {
+    payload.map(|Json(payload)| payload).map_err(|e| api_error(StatusCode::BAD_REQUEST, e.body_text()))
+}
+
+/// An address with the portfolios it belongs to and its last known credits balance, unless totals are hidden.
+fn address_json(st: &AppState, address: &str) -> Value {
+    let portfolios = st.portfolios.iter()
+        .filter(|portfolio| portfolio.addresses.iter().any(|a| a == address))
+        .map(|portfolio| portfolio.name.clone())
+        .collect::<Vec<_>>();
+    json!({
+        "address": address,
+        "portfolios": portfolios,
+        "last_balance": st.alerts.balances.get(address).filter(|_| !st.hides_totals()),
+    })
+}
+
+/// What is known about a tracked attestation without asking the endpoint.
+fn attestation_json(st: &AppState, tracked: &TrackedTransaction) -> Value {
+    let txid = tracked.id.as_str();
+    let pending = st.pending.iter().find(|pending| pending.id == txid);
+    let report = st.reports.get(txid);
+    // Attestations resolved before outcomes were recorded only show as accepted through their report.
+    let status = match (pending, tracked.resolution, report) {
+        (Some(_), ..) => "pending",
+        (None, Some(Resolution::Accepted), _) | (None, None, Some(_)) => "accepted",
+        (None, Some(Resolution::Rejected), _) => "rejected",
+        (None, None, None) => "unknown",
+    };
+    json!({
+        "transaction_id": txid,
+        "kind": tracked.kind,
+        "status": status,
+        "function": pending.map(|pending| pending.function.clone())
+            .or(report.map(|signed| format!("{}/{}", signed.report.program_id, signed.report.function))),
+        "report": report,
+        "verification": st.verifications.get(txid),
+        "breakdown": st.breakdowns.get(txid).filter(|_| !st.hides_totals()),
+    })
+}
+
+/// GET /api/v1/addresses
+pub async fn api_list_addresses(State(state): State<Arc<RwLock<AppState>>>) -> Json<Value> {
+    let st = state.read().await;
+    let addresses = st.addresses.iter().map(|address| address_json(&st, address)).collect::<Vec<_>>();
+    Json(json!({ "addresses": addresses }))
+}
+
+#[derive(Deserialize)]
+pub struct AddressRequest {
+    address: String,
+}
+
+/// POST /api/v1/addresses
+pub async fn api_add_address(
+    State(state): State<Arc<RwLock<AppState>>>,
+    payload: Result<Json<AddressRequest>, JsonRejection>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let address = body(payload)?.address.trim().to_string();
+    if Address::<CurrentNetwork>::from_str(&address).is_err() {
+        return Err(api_error(StatusCode::BAD_REQUEST, format!("'{}' is not a valid address", address)));
+    }
+    let mut st = state.write().await;
+    if st.addresses.contains(&address) {
+        return Err(api_error(StatusCode::CONFLICT, format!("Address {} is already tracked", address)));
+    }
+    st.addresses.push(address.clone());
+    let tracked = st.addresses.len();
+    emit(&mut st, WebhookEvent::AddressSetChanged { added: vec![address.clone()], removed: Vec::new(), tracked }).await;
+    Ok((StatusCode::CREATED, Json(address_json(&st, &address))))
+}
+
+/// DELETE /api/v1/addresses/:address
+pub async fn api_remove_address(
+    State(state): State<Arc<RwLock<AppState>>>,
+    Path(address): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let mut st = state.write().await;
+    if !st.addresses.contains(&address) {
+        return Err(api_error(StatusCode::NOT_FOUND, format!("Address {} is not tracked", address)));
+    }
+    st.addresses.retain(|a| a != &address);
+    let tracked = st.addresses.len();
+    emit(&mut st, WebhookEvent::AddressSetChanged { added: Vec::new(), removed: vec![address], tracked }).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /api/v1/attestations
+pub async fn api_list_attestations(State(state): State<Arc<RwLock<AppState>>>) -> Json<Value> {
+    let st = state.read().await;
+    let attestations = st.transactions.iter().map(|tracked| attestation_json(&st, tracked)).collect::<Vec<_>>();
+    Json(json!({ "attestations": attestations }))
+}
+
+/// GET /api/v1/attestations/:txid, with the details resolved from the endpoint.
+pub async fn api_get_attestation(
+    State(state): State<Arc<RwLock<AppState>>>,
+    Path(txid): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    let (mut attestation, private_key, endpoint) = {
+        let st = state.read().await;
+        let Some(tracked) = st.transactions.iter().find(|tracked| tracked.id == txid) else {
+            return Err(api_error(StatusCode::NOT_FOUND, format!("Transaction {} is not a tracked attestation", txid)));
+        };
+        (attestation_json(&st, tracked), st.private_key.clone(), st.endpoint.clone())
+    };
+    let details = tokio::task::spawn_blocking(move || get_attestation_details(&txid, &private_key, &endpoint, NETWORK_NAME))
+        .await
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, format!("Lookup task failed: {:?}", e)))?
+        .map_err(|e| api_error(StatusCode::BAD_GATEWAY, format!("Failed to resolve the attestation: {}", e)))?;
+    attestation["details"] = json!({
+        "height": details.height,
+        "timestamp": details.unix_timestamp,
+        "data_hash": details.data_hash,
+        "label": result_label(&details.result),
+        "result": details.result.to_string(),
+        "asset": details.result.asset(),
+        "total": details.result.total().map(|total| total.to_string()),
+        "mapping": details.entry,
+    });
+    Ok(Json(attestation))
+}
+
+/// POST /api/v1/runs
+pub async fn api_trigger_run(State(state): State<Arc<RwLock<AppState>>>) -> (StatusCode, Json<Job>) {
+    let mut st = state.write().await;
+    let job = Job {
+        id: format!("{:016x}", rand::random::<u64>()),
+        status: JobStatus::Queued,
+        requested: chrono::Utc::now().timestamp(),
+        started: None,
+        finished: None,
+        run_id: None,
+        error: None,
+        transactions: Vec::new(),
+    };
+    // A full channel already has a run coming, which takes up the job.
+    let _ = st.task_tx.try_send(BackgroundTaskMsg::RunNow);
+    st.jobs.push(job.clone());
+    while st.jobs.len() > MAX_JOBS {
+        match st.jobs.iter().position(|job| matches!(job.status, JobStatus::Succeeded | JobStatus::Failed)) {
+            Some(index) => st.jobs.remove(index),
+            None => break,
+        };
+    }
+    (StatusCode::ACCEPTED, Json(job))
+}
+
+/// GET /api/v1/runs
+pub async fn api_list_runs(State(state): State<Arc<RwLock<AppState>>>) -> Json<Value> {
+    Json(json!({ "runs": state.read().await.jobs }))
+}
+
+/// GET /api/v1/runs/:id
+pub async fn api_get_run(
+    State(state): State<Arc<RwLock<AppState>>>,
+    Path(id): Path<String>,
+) -> Result<Json<Job>, ApiError> {
+    match state.read().await.jobs.iter().find(|job| job.id == id) {
+        Some(job) => Ok(Json(job.clone())),
+        None => Err(api_error(StatusCode::NOT_FOUND, format!("No run {}", id))),
+    }
+}
+
+/// GET /api/v1/config, leaving out the keys and webhook secrets.
+pub async fn api_get_config(State(state): State<Arc<RwLock<AppState>>>) -> Result<Json<Value>, ApiError> {
+    let st = state.read().await;
+    let prover = PrivateKey::<CurrentNetwork>::from_str(&st.private_key)
+        .and_then(Address::try_from)
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let webhooks = st.webhooks.iter().map(|webhook| json!({ "url": webhook.url, "events": webhook.events })).collect::<Vec<_>>();
+    Ok(Json(json!({
+        "network": NETWORK_NAME,
+        "prover": prover.to_string(),
+        "endpoints": st.endpoints,
+        "light_verification": st.light_verification,
+        "inclusion_quorum": st.inclusion_quorum,
+        "mode": st.mode,
+        "solvency_threshold": st.solvency_threshold,
+        "include_stake": st.include_stake,
+        "cadence": st.cadence,
+        "max_attestation_age": st.max_attestation_age,
+        "priority_fee": st.priority_fee,
+        "assets": st.assets,
+        "portfolios": st.portfolios,
+        "view_keys": st.view_keys.len(),
+        "currency": st.currency,
+        "price_feed": st.price_feed.is_some(),
+        "webhooks": webhooks,
+        "alerts": st.alert_rules,
+    })))
+}
@@ -0,0 +1,65 @@
+use axum::{extract::{Path, State}, http::{header, StatusCode}, response::IntoResponse};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::error;
+use crate::evidence::export_bundle;
+use crate::state::AppState;
+use crate::storage::{load_bundle, save_bundle};
+use crate::NETWORK_NAME;
+
+type EvidenceResponse = ([(header::HeaderName, &'static str); 2], String);
+
+/// The stored bundle of the attestation. An accepted transaction never changes, so neither does its bundle.
+async fn stored_bundle(txid: &str, dir: &str) -> Result<EvidenceResponse, (StatusCode, String)> {
+    load_bundle(txid, dir).await
+        .map(|bundle| ([(header::CONTENT_TYPE, "application/json"), (header::CACHE_CONTROL, "public, max-age=31536000, immutable")], bundle))
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read the evidence bundle for {}: {}", txid, e)))
+}
+
+/// GET /evidence/:txid, building and storing the bundle from the endpoint the first time.
+pub async fn get_evidence(
+    State(state): State<Arc<RwLock<AppState>>>,
+    Path(txid): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let txid = txid.trim_end_matches(".json").to_string();
+    let (stored, private_key, endpoint, dir) = {
+        let st = state.read().await;
+        if !st.transactions.iter().any(|tracked| tracked.id == txid) {
+            return Err((StatusCode::NOT_FOUND, format!("Transaction {} is not a tracked attestation", txid)));
+        }
+        (st.evidence.contains(&txid), st.private_key.clone(), st.endpoint.clone(), st.evidence_dir.clone())
+    };
+    if stored {
+        return stored_bundle(&txid, &dir).await;
+    }
+    let id = txid.clone();
+    let bundle = tokio::task::spawn_blocking(move || export_bundle(&id, &private_key, &endpoint, NETWORK_NAME))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Export task failed: {:?}", e)))?
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Failed to export evidence bundle: {}", e)))?;
+    match save_bundle(&bundle, &dir).await {
+        Ok(()) => {
+            state.write().await.evidence.insert(txid);
+        }
+        Err(e) => error!("Failed to store the evidence bundle for {}: {}", txid, e),
+    }
+    let bundle = serde_json::to_string(&bundle)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to serialize the evidence bundle: {}", e)))?;
+    Ok(([(header::CONTENT_TYPE, "application/json"), (header::CACHE_CONTROL, "public, max-age=31536000, immutable")], bundle))
+}
+
+/// GET /evidence/:txid on the public site. Only stored bundles are served, so visitors never make the
+/// tracker call the endpoint.
+pub async fn get_public_evidence(
+    State(state): State<Arc<RwLock<AppState>>>,
+    Path(txid): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let txid = txid.trim_end_matches(".json");
+    let st = state.read().await;
+    if !st.evidence.contains(txid) {
+        return Err((StatusCode::NOT_FOUND, format!("No evidence bundle for transaction {}", txid)));
+    }
+    let dir = st.evidence_dir.clone();
+    drop(st);
+    stored_bundle(txid, &dir).await
+}
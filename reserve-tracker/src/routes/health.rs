@@ -0,0 +1,75 @@
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::Utc;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use std::time::Duration;
+use crate::background::{task_alive, vm_ready};
+use crate::state::AppState;
+use crate::utilities::get_latest_height_within;
+use crate::NETWORK_NAME;
+
+/// How long the endpoint check may take before the endpoint counts as unreachable.
+const ENDPOINT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// GET /healthz
+pub async fn get_healthz() -> &'static str {
+    "ok"
+}
+
+/// GET /readyz
+pub async fn get_readyz(State(state): State<Arc<RwLock<AppState>>>) -> (StatusCode, Json<serde_json::Value>) {
+    let st = state.read().await;
+    let endpoints = st.endpoints.clone();
+    let last_run = st.last_run.clone();
+    let last_success = st.last_success;
+    // Before the first success, the age counts from startup, so a tracker that never attests goes red.
+    let age = (Utc::now() - last_success.unwrap_or(st.started)).num_seconds().max(0) as u64;
+    let max_age = st.max_attestation_age;
+    drop(st);
+
+    // Check every configured endpoint at once; only the first, which attestations go through,
+    // gates readiness.
+    let probes = endpoints.iter()
+        .map(|endpoint| {
+            let url = endpoint.clone();
+            tokio::task::spawn_blocking(move || get_latest_height_within(&url, NETWORK_NAME, ENDPOINT_TIMEOUT))
+        })
+        .collect::<Vec<_>>();
+    let mut checks = Vec::new();
+    for (endpoint, probe) in endpoints.into_iter().zip(probes) {
+        let latest_height = match probe.await {
+            Ok(Ok(height)) => Some(height),
+            _ => None,
+        };
+        checks.push((endpoint, latest_height));
+    }
+    let endpoint_reachable = checks.first().is_some_and(|(_, height)| height.is_some());
+
+    let vm_ready = vm_ready();
+    let task_alive = task_alive();
+    let attestation_fresh = age <= max_age;
+    let ready = vm_ready && task_alive && endpoint_reachable && attestation_fresh;
+
+    let body = json!({
+        "ready": ready,
+        "vm_ready": vm_ready,
+        "background_task_alive": task_alive,
+        "endpoint_reachable": endpoint_reachable,
+        "endpoints": checks.iter().map(|(endpoint, height)| json!({
+            "url": endpoint,
+            "reachable": height.is_some(),
+            "latest_height": height,
+        })).collect::<Vec<_>>(),
+        "last_success": last_success.map(|time| time.to_rfc3339()),
+        "last_success_age_seconds": age,
+        "max_attestation_age_seconds": max_age,
+        "last_run": last_run.map(|run| json!({
+            "finished": run.finished.to_rfc3339(),
+            "success": run.error.is_none(),
+            "error": run.error,
+        })),
+    });
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(body))
+}
@@ -0,0 +1,104 @@
+use axum::{response::Html, extract::{Form, State}};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use html_escape::encode_text;
+use serde::Deserialize;
+use tokio::task::spawn_blocking;
+use crate::state::AppState;
+use crate::NETWORK_NAME;
+use crate::utilities::get_attestation_details;
+use crate::routes::result_label;
+use crate::liabilities::anchored_commitment;
+use crate::routes::liabilities::{render_proof, NO_CUSTOMER_HTML};
+
+#[derive(Deserialize)]
+pub struct InclusionFormData {
+    id: String,
+    salt: String,
+}
+
+/// GET /inclusion
+pub async fn get_inclusion_page() -> Html<String> {
+    render_inclusion_page(String::new())
+}
+
+/// POST /inclusion
+pub async fn handle_inclusion_form(
+    State(state): State<Arc<RwLock<AppState>>>,
+    Form(form): Form<InclusionFormData>,
+) -> Html<String> {
+    let st = state.read().await;
+    let id = form.id.trim();
+
+    let tree = match &st.liabilities {
+        Some(tree) => tree,
+        None => return render_inclusion_page("<p>No liabilities have been committed yet.</p>".to_string()),
+    };
+    let proof = match tree.proof(id, &form.salt) {
+        Some(proof) => proof,
+        None => return render_inclusion_page(NO_CUSTOMER_HTML.to_string()),
+    };
+
+    // Check the proof against the commitment anchored on-chain, and link the reserves attestation from
+    // the same run, so the customer can compare both sides. Both are read off the state lock, as the
+    // endpoint can be slow.
+    let (anchors, private_key, endpoint) = (st.liability_transactions.clone(), st.private_key.clone(), st.endpoint.clone());
+    drop(st);
+    let (anchored, reserves) = spawn_blocking(move || {
+        let anchored = anchored_commitment(&anchors, &private_key, &endpoint, NETWORK_NAME);
+        let reserves = anchored.as_ref().ok().and_then(|commitment| commitment.as_ref()?.reserves.clone()).map(|txid| {
+            let details = get_attestation_details(&txid, &private_key, &endpoint, NETWORK_NAME).map_err(|e| e.to_string());
+            (txid, details)
+        });
+        (anchored, reserves)
+    })
+    .await
+    .unwrap_or_else(|e| (Err(e.to_string()), None));
+
+    let reserves_html = match (&anchored, reserves) {
+        (_, Some((txid, Ok(details)))) => format!(
+            r#"<p><b>Reserves Attestation:</b> {}</p>
+                <p><b>Attestation Height:</b> {}</p>
+                <p><b>Data Hash:</b> {}</p>
+                <p><b>Reserves {}:</b> {}</p>"#,
+            encode_text(&txid), details.height, details.data_hash, result_label(&details.result), details.result
+        ),
+        (_, Some((txid, Err(e)))) => format!(
+            "<p><b>Reserves Attestation:</b> {} (failed to resolve: {})</p>",
+            encode_text(&txid), encode_text(&e)
+        ),
+        (Ok(Some(_)), None) => "<p>No reserves attestation was recorded with this liabilities commitment.</p>".to_string(),
+        _ => String::new(),
+    };
+
+    render_inclusion_page(format!(
+        r#"<div style="margin-top:10px; border:1px solid #ccc; padding:10px;">
+                    <p><b>Your Balance:</b> {}</p>
+                    {}
+                    {}
+                </div>"#,
+        proof.balance,
+        reserves_html,
+        render_proof(&proof, id, &form.salt, &anchored)
+    ))
+}
+
+fn render_inclusion_page(result_html: String) -> Html<String> {
+    Html(format!(
+        r#"
+        <html>
+            <head><title>Check Your Inclusion</title></head>
+            <body>
+                <h3>Check Your Inclusion in Our Liabilities</h3>
+                <form action="/inclusion" method="post" style="margin-top:20px;">
+                    <input type="text" name="id" style="width:300px;" placeholder="Account identifier">
+                    <input type="password" name="salt" style="width:300px;" placeholder="Secret salt">
+                    <button type="submit">Check</button>
+                </form>
+                {}
+            </body>
+        </html>
+        "#,
+        result_html
+    ))
+}
@@ -0,0 +1,115 @@
+use axum::{response::Html, extract::{Form, State}};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use html_escape::encode_text;
+use serde::Deserialize;
+use tokio::task::spawn_blocking;
+use crate::liabilities::{anchored_commitment, AnchoredCommitment, InclusionProof};
+use crate::state::AppState;
+use crate::NETWORK_NAME;
+
+#[derive(Deserialize)]
+pub struct LiabilitiesFormData {
+    id: String,
+    salt: String,
+}
+
+/// GET /liabilities
+pub async fn get_liabilities_page(State(state): State<Arc<RwLock<AppState>>>) -> Html<String> {
+    render_liabilities_page(&*state.read().await, String::new())
+}
+
+/// POST /liabilities
+pub async fn handle_liabilities_form(
+    State(state): State<Arc<RwLock<AppState>>>,
+    Form(form): Form<LiabilitiesFormData>,
+) -> Html<String> {
+    let id = form.id.trim();
+    let st = state.read().await;
+    let proof = match st.liabilities.as_ref().map(|tree| tree.proof(id, &form.salt)) {
+        Some(Some(proof)) => proof,
+        Some(None) => return render_liabilities_page(&st, NO_CUSTOMER_HTML.to_string()),
+        None => return render_liabilities_page(&st, String::new()),
+    };
+    let (anchors, private_key, endpoint) = (st.liability_transactions.clone(), st.private_key.clone(), st.endpoint.clone());
+    drop(st);
+    let anchored = spawn_blocking(move || anchored_commitment(&anchors, &private_key, &endpoint, NETWORK_NAME))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+    let proof_html = render_proof(&proof, id, &form.salt, &anchored);
+    render_liabilities_page(&*state.read().await, proof_html)
+}
+
+/// Shown when no customer matches the identifier and salt.
+pub(crate) const NO_CUSTOMER_HTML: &str = "<p>No customer found with that identifier and salt.</p>";
+
+/// Whether the proof verifies against the root and total anchored on-chain, followed by the proof itself.
+/// The tree that generated the proof is not checked against, as it would always verify.
+pub(crate) fn render_proof(proof: &InclusionProof, id: &str, salt: &str, anchored: &Result<Option<AnchoredCommitment>, String>) -> String {
+    let verification = match anchored {
+        Ok(Some(commitment)) => format!(
+            r#"<p><b>Verifies against the on-chain root:</b> {}</p>
+                    <p><b>On-chain Root:</b> {} (block {}, transaction {})</p>
+                    <p><b>On-chain Total Liabilities:</b> {}</p>"#,
+            if proof.verify(id, salt, &commitment.root, commitment.total).unwrap_or(false) { "yes" } else { "no" },
+            encode_text(&commitment.root), encode_text(&commitment.height), encode_text(&commitment.transaction_id), commitment.total
+        ),
+        Ok(None) => "<p><b>Verifies against the on-chain root:</b> not anchored on-chain yet</p>".to_string(),
+        Err(e) => format!(
+            "<p><b>Verifies against the on-chain root:</b> unknown ({})</p>",
+            encode_text(e)
+        ),
+    };
+    let json = serde_json::to_string_pretty(proof).unwrap_or_default();
+    format!(
+        r#"{}
+                    <pre>{}</pre>"#,
+        verification,
+        encode_text(&json)
+    )
+}
+
+fn render_liabilities_page(st: &AppState, proof_html: String) -> Html<String> {
+    let body = match &st.liabilities {
+        Some(tree) => format!(
+            r#"<p><b>Root:</b> {}</p>
+                <p><b>Total Liabilities:</b> {}</p>
+                <p><b>Customers:</b> {}</p>
+                <form action="/liabilities" method="post" style="margin-top:20px;">
+                    <input type="text" name="id" style="width:300px;" placeholder="Customer identifier">
+                    <input type="text" name="salt" style="width:300px;" placeholder="Customer salt">
+                    <button type="submit">Generate Proof</button>
+                </form>
+                {}"#,
+            tree.root(), tree.total(), tree.num_customers(), proof_html
+        ),
+        None => "<p>No customer balance file loaded.</p>".to_string(),
+    };
+    let anchors = if st.liability_transactions.is_empty() {
+        "<li>Not anchored on-chain yet.</li>".to_string()
+    } else {
+        st.liability_transactions
+            .iter()
+            .map(|anchor| format!("<li>{}</li>", encode_text(&anchor.id)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    Html(format!(
+        r#"
+        <html>
+            <head><title>Liabilities</title></head>
+            <body>
+                <h3>Liabilities Commitment</h3>
+                {}
+                <h3>On-chain Commitments (Transactions)</h3>
+                <ul>
+                    {}
+                </ul>
+                <p><a href="/">Back to Addresses</a></p>
+            </body>
+        </html>
+        "#,
+        body, anchors
+    ))
+}
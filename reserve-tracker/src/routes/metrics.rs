@@ -0,0 +1,14 @@
+use axum::{extract::State, http::header, response::IntoResponse};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use crate::metrics::METRICS;
+use crate::state::AppState;
+
+/// GET /metrics
+pub async fn get_metrics(State(state): State<Arc<RwLock<AppState>>>) -> impl IntoResponse {
+    let addresses = state.read().await.addresses.len();
+    (
+        [(header::CONTENT_TYPE, "application/openmetrics-text; version=1.0.0; charset=utf-8")],
+        METRICS.encode(addresses),
+    )
+}
@@ -3,3 +3,27 @@ pub use addresses::*;
 
 pub mod transactions;
 pub use transactions::*;
+
+pub mod liabilities;
+pub use liabilities::*;
+
+pub mod inclusion;
+pub use inclusion::*;
+
+pub mod metrics;
+pub use metrics::*;
+
+pub mod health;
+pub use health::*;
+
+pub mod reports;
+pub use reports::*;
+
+pub mod evidence;
+pub use evidence::*;
+
+pub mod public;
+pub use public::*;
+
+pub mod api;
+pub use api::*;
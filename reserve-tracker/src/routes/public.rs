@@ -0,0 +1,62 @@
+use axum::{extract::{Query, State}, http::header, response::{Html, IntoResponse}, Json};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use crate::site::{history, latest_status, signed, render_atom_feed, render_badge, render_public_page, Status};
+use crate::state::AppState;
+
+/// GET / on the public port, and GET /public
+pub async fn get_public_page(State(state): State<Arc<RwLock<AppState>>>) -> impl IntoResponse {
+    let st = state.read().await;
+    let history = history(&st.transactions, &st.reports);
+    let no_breakdowns = HashMap::new();
+    let breakdowns = if st.hides_totals() { &no_breakdowns } else { &st.breakdowns };
+    let page = render_public_page(&history, &st.verifications, breakdowns, Some(&st.evidence), chrono::Utc::now().timestamp());
+    ([(header::CACHE_CONTROL, "public, max-age=60")], Html(page))
+}
+
+/// GET /feed.atom
+pub async fn get_atom_feed(State(state): State<Arc<RwLock<AppState>>>) -> impl IntoResponse {
+    let st = state.read().await;
+    let history = history(&st.transactions, &st.reports);
+    let feed = render_atom_feed(&history, &st.reports, Some(&st.evidence), chrono::Utc::now().timestamp());
+    ([(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8"), (header::CACHE_CONTROL, "public, max-age=60")], feed)
+}
+
+#[derive(Deserialize)]
+pub struct StatusQuery {
+    /// The asset to show; credits, or the latest attestation of any asset, if not given.
+    asset: Option<String>,
+}
+
+async fn current_status(state: &Arc<RwLock<AppState>>, asset: Option<&str>) -> Option<Status> {
+    let st = state.read().await;
+    latest_status(&signed(&history(&st.transactions, &st.reports)), &st.verifications, asset, st.max_attestation_age, chrono::Utc::now().timestamp())
+}
+
+/// GET /badge.svg
+pub async fn get_badge(
+    State(state): State<Arc<RwLock<AppState>>>,
+    Query(query): Query<StatusQuery>,
+) -> impl IntoResponse {
+    let status = current_status(&state, query.asset.as_deref()).await;
+    (
+        [(header::CONTENT_TYPE, "image/svg+xml; charset=utf-8"), (header::CACHE_CONTROL, "public, max-age=60")],
+        render_badge(status.as_ref()),
+    )
+}
+
+/// GET /widget.json
+pub async fn get_widget(
+    State(state): State<Arc<RwLock<AppState>>>,
+    Query(query): Query<StatusQuery>,
+) -> impl IntoResponse {
+    let status = current_status(&state, query.asset.as_deref()).await;
+    // Partner sites fetch the widget from their own origin.
+    (
+        [(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*"), (header::CACHE_CONTROL, "public, max-age=60")],
+        Json(json!({ "status": status })),
+    )
+}
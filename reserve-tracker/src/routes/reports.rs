@@ -0,0 +1,18 @@
+use axum::{extract::{Path, State}, http::StatusCode, Json};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use crate::reports::SignedReport;
+use crate::state::AppState;
+
+/// GET /reports/:txid
+pub async fn get_report(
+    State(state): State<Arc<RwLock<AppState>>>,
+    Path(txid): Path<String>,
+) -> Result<Json<SignedReport>, (StatusCode, String)> {
+    // Accept the `.json` suffix, so the report can be linked to as a file.
+    let txid = txid.trim_end_matches(".json");
+    match state.read().await.reports.get(txid) {
+        Some(report) => Ok(Json(report.clone())),
+        None => Err((StatusCode::NOT_FOUND, format!("No signed report for transaction {}", txid))),
+    }
+}
@@ -1,16 +1,21 @@
-use std::str::FromStr;
 use axum::{
     response::Html,
     extract::{Form, Query, State},
 };
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use serde::Deserialize;
 use crate::state::AppState;
 use html_escape::encode_text;
-use snarkvm::prelude::{Output, Argument, Transition, Address, PrivateKey};
-use crate::CurrentNetwork;
-use crate::utilities::{get_block_height, get_block_timestamp, get_confirmed_transaction, get_mapping_value};
+use crate::NETWORK_NAME;
+use crate::utilities::{get_attestation_details, AttestationDetails, AttestationResult};
+use crate::prices::{fiat_value, format_minor, format_price, valued_totals};
+use crate::storage::save_verifications;
+use crate::evidence::{check_attestation, check_block_inclusion, Verification};
+use crate::routes::alert_banner;
+use crate::site::{history, history_chart, signed};
+use tracing::error;
 
 #[derive(Deserialize)]
 pub struct TransactionsFormData {
@@ -23,58 +28,194 @@ pub struct TransactionsQuery {
     show: Option<String>,
 }
 
+/// What was resolved for the attestation being shown, away from the state lock.
+struct Shown {
+    details: Result<AttestationDetails, String>,
+    verification: Option<Verification>,
+    /// Whether the verification has a new outcome to store.
+    checked: bool,
+    /// Why the proofs could not be checked this time. Not stored, so the check runs again.
+    proof_pending: Option<String>,
+    /// Why the block inclusion could not be checked this time. Not stored either.
+    inclusion_pending: Option<String>,
+}
+
+/// Resolves the attestation from the endpoint and, if it was not verified on acceptance, verifies its
+/// proofs and, in light verification mode, its block against the endpoints. Blocks on the endpoints.
+fn resolve_shown(
+    txid: &str,
+    private_key: &str,
+    endpoint: &str,
+    endpoints: &[String],
+    inclusion: Option<Option<usize>>,
+    mut verification: Option<Verification>,
+) -> Shown {
+    let details = match get_attestation_details(txid, private_key, endpoint, NETWORK_NAME) {
+        Ok(details) => details,
+        Err(e) => return Shown { details: Err(e.to_string()), verification, checked: false, proof_pending: None, inclusion_pending: None },
+    };
+    let mut checked = false;
+    let mut proof_pending = None;
+    // Attestations are verified on acceptance; this only checks the ones resolved before that or
+    // that could not be checked then.
+    if verification.is_none() {
+        match check_attestation(details.transaction.transaction(), private_key, endpoint) {
+            Ok(checked_now) => {
+                verification = Some(checked_now);
+                checked = true;
+            }
+            Err(e) => proof_pending = Some(e),
+        }
+    }
+    let mut inclusion_pending = None;
+    if let (Some(quorum), Some(verification)) = (inclusion, verification.as_mut().filter(|v| v.inclusion.is_none())) {
+        match check_block_inclusion(&details, endpoints, quorum, NETWORK_NAME) {
+            Ok(inclusion) => {
+                verification.inclusion = Some(inclusion);
+                checked = true;
+            }
+            Err(e) => inclusion_pending = Some(e.to_string()),
+        }
+    }
+    Shown { details: Ok(details), verification, checked, proof_pending, inclusion_pending }
+}
+
 /// GET /transactions
 pub async fn get_transactions_page(
     State(state): State<Arc<RwLock<AppState>>>,
     Query(query): Query<TransactionsQuery>,
 ) -> Html<String> {
     let st = state.read().await;
-    let transactions = &st.transactions;
+    let transactions = st.transactions.clone();
+    let mut verifications = st.verifications.clone();
+    let reports = st.reports.keys().cloned().collect::<HashSet<_>>();
+    let shown_txid = query.show.filter(|txid| transactions.iter().any(|tracked| &tracked.id == txid));
+    let recorded = shown_txid.as_ref().and_then(|txid| st.prices.get(txid)).cloned().unwrap_or_default();
+    let (private_key, endpoint, endpoints) = (st.private_key.clone(), st.endpoint.clone(), st.endpoints.clone());
+    let inclusion = st.light_verification.then_some(st.inclusion_quorum);
+    let banner = alert_banner(&st.alerts);
+    let hides_totals = st.hides_totals();
+    let no_breakdowns = HashMap::new();
+    let chart = history_chart(&signed(&history(&st.transactions, &st.reports)), if hides_totals { &no_breakdowns } else { &st.breakdowns });
+    drop(st);
 
-    let mut transaction_list_items = Vec::new();
+    let shown = match &shown_txid {
+        Some(txid) => {
+            let (txid, verification) = (txid.clone(), verifications.get(txid).cloned());
+            let endpoints = endpoints.clone();
+            let shown = tokio::task::spawn_blocking(move || {
+                resolve_shown(&txid, &private_key, &endpoint, &endpoints, inclusion, verification)
+            }).await;
+            Some(shown.unwrap_or_else(|e| Shown {
+                details: Err(format!("lookup task failed: {:?}", e)),
+                verification: None,
+                checked: false,
+                proof_pending: None,
+                inclusion_pending: None,
+            }))
+        }
+        None => None,
+    };
+    if let (Some(txid), Some(Shown { verification: Some(verification), checked: true, .. })) = (&shown_txid, &shown) {
+        verifications.insert(txid.clone(), verification.clone());
+        let mut st = state.write().await;
+        st.verifications.insert(txid.clone(), verification.clone());
+        if let Err(e) = save_verifications(&st.verifications, &st.verifications_file).await {
+            error!("Failed to save verifications: {}", e);
+        }
+    }
 
-    for txid in transactions {
-        let safe_txid = encode_text(txid);
-        let mut details_html = String::new();
-        if Some(txid) == query.show.as_ref() {
-            let height = get_block_height::<CurrentNetwork>(txid, &st.endpoint, "testnet").unwrap();
-            let block_timestamp = get_block_timestamp::<CurrentNetwork>(&height, &st.endpoint, "testnet").unwrap();
-            let transaction = get_confirmed_transaction::<CurrentNetwork>(txid, &st.endpoint, "testnet").unwrap().clone();
-            // Pull out the data hash from the first argument of the future in the output of the first transition.
-            let transition: Transition<CurrentNetwork> = transaction.execution().unwrap().transitions().next().unwrap().clone();
-            let data_hash = match transition.outputs().get(0) {
-                Some(Output::Future(_, Some(future))) => {
-                    match future.arguments().get(0).unwrap().clone() {
-                        Argument::Plaintext(plaintext) => plaintext.to_string(),
-                        _ => "Could not find data hash.".to_string()
-                    }
-                }
-                _ => "Could not find data hash.".to_string()
+    let details_html = match &shown {
+        Some(Shown { details: Ok(details), verification, proof_pending, inclusion_pending, .. }) => {
+            let proof_html = match (verification, proof_pending) {
+                (Some(Verification { verified: true, .. }), _) => "verified locally against the program's verifying keys".to_string(),
+                (Some(Verification { error, .. }), _) => format!("NOT verified ({})", encode_text(error.as_deref().unwrap_or("unknown error"))),
+                (None, Some(reason)) => format!("not checked yet; {}", encode_text(reason)),
+                (None, None) => "not checked yet".to_string(),
             };
-            // Construct a query for the total balance at that point in time.
-            let address = Address::<CurrentNetwork>::try_from(PrivateKey::from_str(&st.private_key).unwrap()).unwrap();
-            let raw_string = format!("{{user:{address},hash:{data_hash},height:{height}u32}}");
-            let total_balance = get_mapping_value::<CurrentNetwork>("proof_of_reserves_v0_1_0.aleo", "data", raw_string.as_str(), &st.endpoint, "testnet").unwrap();
-
-            details_html = format!(r#"
+            let inclusion_html = match (verification.as_ref().and_then(|v| v.inclusion.as_ref()), inclusion_pending) {
+                (Some(inclusion), _) if inclusion.included => format!(
+                    "<p><b>Inclusion:</b> in block {}, agreed by {} of {} endpoints</p>",
+                    encode_text(inclusion.block_hash.as_deref().unwrap_or_default()),
+                    inclusion.endpoints.len(), endpoints.len()
+                ),
+                (Some(inclusion), _) => format!(
+                    r#"<p style="color:red;"><b>Inclusion:</b> FAILED ({})</p>"#,
+                    encode_text(inclusion.error.as_deref().unwrap_or("unknown error"))
+                ),
+                (None, Some(reason)) => format!(
+                    r#"<p style="color:red;"><b>Inclusion:</b> NOT CHECKED ({})</p>"#,
+                    encode_text(reason)
+                ),
+                (None, None) => String::new(),
+            };
+            // Prices are recorded when the attestation is accepted; no valuation is shown in solvency mode.
+            let fiat_html = valued_totals(&details.result)
+                .into_iter()
+                .filter(|_| !hides_totals)
+                .map(|(asset, total, decimals)| match recorded.iter().find(|recorded| recorded.asset == asset) {
+                    Some(recorded) => match fiat_value(total, decimals, recorded.price_micros) {
+                        Some(value) => format!(
+                            "<p><b>Fiat Value:</b> {} {} (at {} {} per {})</p>",
+                            format_minor(value), encode_text(&recorded.currency),
+                            format_price(recorded.price_micros), encode_text(&recorded.currency), encode_text(&recorded.asset)
+                        ),
+                        None => format!("<p><b>Fiat Value:</b> unavailable (the {} value overflows)</p>", encode_text(&asset)),
+                    },
+                    None => format!("<p><b>Fiat Value:</b> unavailable (no {} price was recorded on acceptance)</p>", encode_text(&asset)),
+                })
+                .collect::<String>();
+            format!(r#"
             <div style="margin-top:10px; border:1px solid #ccc; padding:10px;">
                 <p><b>Transaction Height:</b> {}</p>
                 <p><b>Timestamp:</b> {}</p>
-                <p><b>Total Balance:</b> {}</p>
+                <p><b>{}:</b> {}</p>
+                {}
+                <p><b>Proof:</b> {}</p>
+                {}
             </div>
-            "#, height, block_timestamp, total_balance);
+            "#, details.height, details.timestamp, result_label(&details.result), details.result, fiat_html, proof_html, inclusion_html)
         }
+        Some(Shown { details: Err(e), .. }) => format!(r#"
+            <div style="margin-top:10px; border:1px solid #ccc; padding:10px;">
+                <p>Failed to resolve transaction: {}</p>
+            </div>
+            "#, encode_text(e)),
+        None => String::new(),
+    };
 
+    let mut transaction_list_items = Vec::new();
+    for tracked in &transactions {
+        let txid = &tracked.id;
+        let safe_txid = encode_text(txid);
+        let report_link = if reports.contains(txid) {
+            format!(r#"<a href="/reports/{safe_txid}.json" style="margin-left:10px;">Signed report</a>"#)
+        } else {
+            String::new()
+        };
+        let evidence_link = format!(r#"<a href="/evidence/{safe_txid}.json" style="margin-left:10px;">Evidence bundle</a>"#);
+        let badge = match verifications.get(txid) {
+            Some(checked) if checked.passed() => r#"<span style="color:green;margin-left:10px;">&#10004; Verified</span>"#,
+            Some(Verification { verified: true, .. }) => r#"<span style="color:red;margin-left:10px;">&#10008; Inclusion check failed</span>"#,
+            Some(_) => r#"<span style="color:red;margin-left:10px;">&#10008; Verification failed</span>"#,
+            None => "",
+        };
+        let details_html = if Some(txid) == shown_txid.as_ref() { details_html.as_str() } else { "" };
+        let kind = tracked.kind.label();
         transaction_list_items.push(format!(
             r#"<li>
                 <form action="/transactions" method="get" style="display:inline;">
                     <input type="hidden" name="show" value="{safe_txid}">
                     <button type="submit" style="border:none;background:none;color:blue;text-decoration:underline;cursor:pointer;">{safe_txid}</button>
                 </form>
+                <span style="margin-left:10px;">{kind}</span>
+                {badge}
                 <form action="/transactions" method="post" style="display:inline;margin-left:10px;">
                     <input type="hidden" name="txid" value="{safe_txid}">
                     <button type="submit" name="action" value="remove">Remove</button>
                 </form>
+                {report_link}
+                {evidence_link}
                 {details_html}
             </li>"#
         ));
@@ -91,6 +232,9 @@ pub async fn get_transactions_page(
         <html>
             <head><title>Verification History</title></head>
             <body>
+                {}
+                <h3>Reserve History</h3>
+                {}
                 <h3>Verification History (Transactions)</h3>
                 <ul>
                     {}
@@ -99,25 +243,33 @@ pub async fn get_transactions_page(
             </body>
         </html>
         "#,
-        tx_list
+        banner, chart, tx_list
     ))
 }
 
+/// The label for an attestation result, since solvency mode does not record the raw total.
+pub fn result_label(result: &AttestationResult) -> &'static str {
+    match result {
+        AttestationResult::Total(_) | AttestationResult::Breakdown { .. } => "Total Balance",
+        AttestationResult::PrivateTotal(_) => "Private Records Balance",
+        AttestationResult::TokenTotal { .. } => "Token Balance",
+        AttestationResult::ExternalTotals(_) => "External Balances",
+        AttestationResult::Solvency { .. } => "Solvency",
+    }
+}
+
 /// POST /transactions
 pub async fn handle_transactions_form(
     State(state): State<Arc<RwLock<AppState>>>,
     Form(form): Form<TransactionsFormData>,
 ) -> Html<String> {
     let mut st = state.write().await;
-    match form.action.as_str() {
-        "remove" => {
-            if let Some(txid) = form.txid {
-                st.transactions.retain(|t| t != &txid);
-                // Save transactions if needed immediately:
-                // crate::storage::save_transactions(&st.transactions, &st.transactions_file).await.ok();
-            }
+    if form.action == "remove" {
+        if let Some(txid) = form.txid {
+            st.transactions.retain(|t| t.id != txid);
+            // Save transactions if needed immediately:
+            // crate::storage::save_transactions(&st.transactions, &st.transactions_file).await.ok();
         }
-        _ => (),
     }
     drop(st);
     get_transactions_page(State(state), Query(TransactionsQuery { show: None })).await
@@ -0,0 +1,509 @@
+use html_escape::{encode_double_quoted_attribute, encode_text};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::evidence::{export_bundle, Verification};
+use crate::reports::{AttestationReport, SignedReport};
+use crate::state::{Resolution, TrackedTransaction};
+
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 200.0;
+const CHART_PADDING: f64 = 30.0;
+
+/// An attestation in the history, with its signed report if it has one.
+#[derive(Clone, Copy, Debug)]
+pub enum Attestation<'a> {
+    Signed(&'a AttestationReport),
+    /// A tracked attestation without a report: one still pending, one that was rejected, or an
+    /// accepted one whose report has not been signed yet.
+    Unsigned(&'a TrackedTransaction),
+}
+
+impl<'a> Attestation<'a> {
+    pub fn transaction_id(&self) -> &'a str {
+        match self {
+            Attestation::Signed(report) => &report.transaction_id,
+            Attestation::Unsigned(tracked) => &tracked.id,
+        }
+    }
+
+    pub fn report(&self) -> Option<&'a AttestationReport> {
+        match self {
+            Attestation::Signed(report) => Some(report),
+            Attestation::Unsigned(_) => None,
+        }
+    }
+
+    /// Whether the attestation was accepted, which only signed ones are known to be until resolved.
+    pub fn accepted(&self) -> bool {
+        match self {
+            Attestation::Signed(_) => true,
+            Attestation::Unsigned(tracked) => tracked.resolution == Some(Resolution::Accepted),
+        }
+    }
+
+    /// Where the attestation is: accepted, rejected, or pending confirmation.
+    pub fn status(&self) -> &'static str {
+        match self {
+            Attestation::Signed(_) => "accepted",
+            Attestation::Unsigned(tracked) => match tracked.resolution {
+                Some(Resolution::Accepted) => "accepted",
+                Some(Resolution::Rejected) => "rejected",
+                None => "pending",
+            },
+        }
+    }
+}
+
+/// The tracked attestations and any other signed reports, oldest first. Signed reports are ordered
+/// by block, and each unsigned attestation follows the signed one tracked before it.
+pub fn history<'a>(transactions: &'a [TrackedTransaction], reports: &'a HashMap<String, SignedReport>) -> Vec<Attestation<'a>> {
+    let mut signed = reports.values().map(|signed| &signed.report).collect::<Vec<_>>();
+    signed.sort_by(|a, b| (a.block_height, &a.transaction_id).cmp(&(b.block_height, &b.transaction_id)));
+
+    // The unsigned attestations, by the signed one tracked before them.
+    let mut unsigned: HashMap<Option<&str>, Vec<&TrackedTransaction>> = HashMap::new();
+    let mut previous = None;
+    for tracked in transactions {
+        match reports.contains_key(&tracked.id) {
+            true => previous = Some(tracked.id.as_str()),
+            false => unsigned.entry(previous).or_default().push(tracked),
+        }
+    }
+    let mut history = unsigned.remove(&None).unwrap_or_default().into_iter().map(Attestation::Unsigned).collect::<Vec<_>>();
+    for report in signed {
+        history.push(Attestation::Signed(report));
+        let after = unsigned.remove(&Some(report.transaction_id.as_str())).unwrap_or_default();
+        history.extend(after.into_iter().map(Attestation::Unsigned));
+    }
+    history
+}
+
+/// The signed reports in the history, oldest first.
+pub fn signed<'a>(history: &[Attestation<'a>]) -> Vec<&'a AttestationReport> {
+    history.iter().filter_map(Attestation::report).collect()
+}
+
+/// What the attestation shows: its total, or the solvency outcome when the total is hidden.
+pub fn summary(report: &AttestationReport) -> String {
+    match (&report.total, report.solvent, &report.threshold) {
+        (Some(total), _, _) => format!("{} {}", total, report.asset),
+        (None, None, None) if !report.totals.is_empty() => report.totals.iter()
+            .map(|(asset, total)| format!("{} {}", total, asset))
+            .collect::<Vec<_>>()
+            .join(", "),
+        (None, Some(true), Some(threshold)) => format!("PASS (reserves >= {})", threshold),
+        (None, Some(false), Some(threshold)) => format!("FAIL (reserves < {})", threshold),
+        _ => "hidden".to_string(),
+    }
+}
+
+/// The verification status of an attestation, with the color it is shown in.
+pub fn verification_status(verification: Option<&Verification>) -> (&'static str, &'static str) {
+    match verification {
+        Some(verification) if verification.passed() => ("Verified", "green"),
+        Some(_) => ("Verification failed", "red"),
+        None => ("Not checked yet", "gray"),
+    }
+}
+
+fn date(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|date| date.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+/// Line charts of the attested total of each asset over time, and of the credits observed in each
+/// portfolio, which no attestation covers.
+pub fn history_chart(history: &[&AttestationReport], breakdowns: &HashMap<String, BTreeMap<String, u128>>) -> String {
+    let charts = history_charts(history, breakdowns);
+    if charts.is_empty() {
+        return "<p>No attested totals to chart yet.</p>".to_string();
+    }
+    charts.into_iter()
+        .map(|(asset, svg)| format!("<figure>\n    <figcaption>{}</figcaption>\n    {}\n</figure>", encode_text(&asset), svg))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// An SVG chart for each asset with an attested total, then for each portfolio tag in the cached
+/// breakdowns, with the name of each. The breakdowns are balances observed when the attestation was
+/// made rather than attested totals, and their charts are labelled as such.
+pub fn history_charts(history: &[&AttestationReport], breakdowns: &HashMap<String, BTreeMap<String, u128>>) -> Vec<(String, String)> {
+    let mut assets: BTreeMap<String, Vec<(i64, u128)>> = BTreeMap::new();
+    let mut tags: BTreeMap<String, Vec<(i64, u128)>> = BTreeMap::new();
+    for report in history {
+        for (asset, total) in report.asset_totals() {
+            assets.entry(asset).or_default().push((report.timestamp, total));
+        }
+        for (tag, total) in breakdowns.get(&report.transaction_id).into_iter().flatten() {
+            tags.entry(format!("credits in {} (observed, not attested)", tag)).or_default().push((report.timestamp, *total));
+        }
+    }
+    let assets = assets.into_iter().map(|(name, points)| {
+        let svg = line_chart(&format!("Attested {} over time", name), &points);
+        (name, svg)
+    });
+    let tags = tags.into_iter().map(|(name, points)| {
+        let svg = line_chart(&format!("Observed {} over time", name), &points);
+        (name, svg)
+    });
+    assets.chain(tags).collect()
+}
+
+/// A line chart of the points, oldest first, described by the label.
+fn line_chart(label: &str, points: &[(i64, u128)]) -> String {
+    let (first, last) = (points[0].0, points[points.len() - 1].0);
+    let max = points.iter().map(|(_, total)| *total).max().unwrap_or(0).max(1);
+    let plot = |(timestamp, total): (i64, u128)| {
+        let x = match last > first {
+            true => CHART_PADDING + (timestamp - first) as f64 / (last - first) as f64 * (CHART_WIDTH - 2.0 * CHART_PADDING),
+            false => CHART_WIDTH / 2.0,
+        };
+        let y = CHART_HEIGHT - CHART_PADDING - total as f64 / max as f64 * (CHART_HEIGHT - 2.0 * CHART_PADDING);
+        (x, y)
+    };
+    let line = points.iter().map(|point| {
+        let (x, y) = plot(*point);
+        format!("{:.1},{:.1}", x, y)
+    }).collect::<Vec<_>>().join(" ");
+    let dots = points.iter().map(|point| {
+        let (x, y) = plot(*point);
+        format!(
+            r#"<circle cx="{:.1}" cy="{:.1}" r="3" fill="steelblue"><title>{}: {}</title></circle>"#,
+            x, y, date(point.0), point.1
+        )
+    }).collect::<String>();
+    let label = encode_double_quoted_attribute(label);
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{CHART_WIDTH}" height="{CHART_HEIGHT}" viewBox="0 0 {CHART_WIDTH} {CHART_HEIGHT}" role="img" aria-label="{label}">
+    <line x1="{CHART_PADDING}" y1="{bottom}" x2="{right}" y2="{bottom}" stroke="#999"/>
+    <line x1="{CHART_PADDING}" y1="{CHART_PADDING}" x2="{CHART_PADDING}" y2="{bottom}" stroke="#999"/>
+    <text x="{CHART_PADDING}" y="{label_y}" font-size="10">{max}</text>
+    <text x="{CHART_PADDING}" y="{CHART_HEIGHT}" font-size="10">{start}</text>
+    <text x="{right}" y="{CHART_HEIGHT}" font-size="10" text-anchor="end">{end}</text>
+    <polyline points="{line}" fill="none" stroke="steelblue" stroke-width="2"/>
+    {dots}
+</svg>"##,
+        bottom = CHART_HEIGHT - CHART_PADDING,
+        right = CHART_WIDTH - CHART_PADDING,
+        label_y = CHART_PADDING - 5.0,
+        start = date(first),
+        end = date(last),
+    )
+}
+
+/// The status of the latest attestation, for embedding on other sites.
+#[derive(Clone, Debug, Serialize)]
+pub struct Status {
+    pub asset: String,
+    /// The attested total, or the solvency outcome when the total is hidden.
+    pub summary: String,
+    pub transaction_id: String,
+    pub block_height: u32,
+    pub timestamp: i64,
+    /// Seconds since the block of the attestation.
+    pub age: i64,
+    pub verification: &'static str,
+    /// `fresh`, `stale` once the attestation is older than the maximum age, and `expired` at twice
+    /// that age or if its verification failed.
+    pub freshness: &'static str,
+}
+
+/// The status of the latest attestation of the asset, or of credits, or of any asset.
+pub fn latest_status(
+    history: &[&AttestationReport],
+    verifications: &HashMap<String, Verification>,
+    asset: Option<&str>,
+    max_age: u64,
+    now: i64,
+) -> Option<Status> {
+    let latest = |asset: &str| history.iter().rev().find(|report| report.asset == asset || report.totals.contains_key(asset));
+    let report = match asset {
+        Some(asset) => latest(asset)?,
+        None => latest("credits").or(history.last())?,
+    };
+    let verification = verifications.get(&report.transaction_id);
+    let age = (now - report.timestamp).max(0);
+    let freshness = match verification {
+        Some(verification) if !verification.passed() => "expired",
+        _ if age > 2 * max_age as i64 => "expired",
+        _ if age > max_age as i64 => "stale",
+        _ => "fresh",
+    };
+    Some(Status {
+        asset: report.asset.clone(),
+        summary: summary(report),
+        transaction_id: report.transaction_id.clone(),
+        block_height: report.block_height,
+        timestamp: report.timestamp,
+        age,
+        verification: verification_status(verification).0,
+        freshness,
+    })
+}
+
+/// A rough age, e.g. `5m`, `3h` or `2d`.
+fn short_age(age: i64) -> String {
+    match age {
+        ..=3599 => format!("{}m", age / 60),
+        3600..=86399 => format!("{}h", age / 3600),
+        _ => format!("{}d", age / 86400),
+    }
+}
+
+/// A flat badge with the latest total and its age, green while fresh, amber when stale, and red
+/// when expired, unverified or missing.
+pub fn render_badge(status: Option<&Status>) -> String {
+    let (message, color) = match status {
+        Some(status) => (
+            format!("{} · {} ago", status.summary, short_age(status.age)),
+            match status.freshness {
+                "fresh" => "#4c1",
+                "stale" => "#dfb317",
+                _ => "#e05d44",
+            },
+        ),
+        None => ("no attestation".to_string(), "#e05d44"),
+    };
+    let label = "proof of reserves";
+    // Verdana at 11px averages about 7px per character.
+    let width = |text: &str| text.chars().count() * 7 + 10;
+    let (label_width, message_width) = (width(label), width(&message));
+    let total = label_width + message_width;
+    let (message, description) = (encode_text(&message), encode_double_quoted_attribute(&message));
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{total}" height="20" role="img" aria-label="{label}: {description}">
+    <title>{label}: {message}</title>
+    <rect width="{label_width}" height="20" fill="#555"/>
+    <rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/>
+    <g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+        <text x="{label_x}" y="14">{label}</text>
+        <text x="{message_x}" y="14">{message}</text>
+    </g>
+</svg>
+"##,
+        label_x = label_width / 2,
+        message_x = label_width + message_width / 2,
+    )
+}
+
+/// The public transparency page. It only reads stored data and has no controls, so it can be cached,
+/// served on its own port, or written out as static HTML. Links are relative, to the reports and
+/// evidence bundles under `reports/` and `evidence/`; with `evidence` given, only those bundles are linked.
+pub fn render_public_page(
+    history: &[Attestation],
+    verifications: &HashMap<String, Verification>,
+    breakdowns: &HashMap<String, BTreeMap<String, u128>>,
+    evidence: Option<&HashSet<String>>,
+    generated: i64,
+) -> String {
+    let reports = signed(history);
+    let latest = match reports.last() {
+        Some(report) => {
+            let (status, color) = verification_status(verifications.get(&report.transaction_id));
+            format!(
+                r#"<p style="font-size:1.4em;"><b>{}</b></p>
+        <p>Attested at block {} on {} by <code>{}/{}</code>. <span style="color:{};">{}</span></p>"#,
+                encode_text(&summary(report)), report.block_height, date(report.timestamp),
+                encode_text(&report.program_id), encode_text(&report.function), color, status
+            )
+        }
+        None => "<p>No attestation has been accepted yet.</p>".to_string(),
+    };
+
+    let rows = history.iter().rev().map(|attestation| {
+        let transaction_id = attestation.transaction_id();
+        let (status, color) = verification_status(verifications.get(transaction_id));
+        let txid = encode_double_quoted_attribute(transaction_id);
+        let evidence_link = match evidence.is_none_or(|evidence| evidence.contains(transaction_id)) {
+            true => format!(r#" <a href="evidence/{txid}.json">Evidence</a>"#),
+            false => String::new(),
+        };
+        let (block, time, result, report_link) = match attestation {
+            Attestation::Signed(report) => (
+                report.block_height.to_string(),
+                date(report.timestamp),
+                encode_text(&summary(report)).to_string(),
+                format!(r#"<a href="reports/{txid}.json">Report</a>"#),
+            ),
+            Attestation::Unsigned(tracked) => {
+                let result = match tracked.resolution {
+                    Some(Resolution::Accepted) => "Accepted; report not signed yet",
+                    Some(Resolution::Rejected) => "Rejected",
+                    None => "Pending confirmation",
+                };
+                ("-".to_string(), "-".to_string(), result.to_string(), String::new())
+            }
+        };
+        format!(
+            r#"<tr>
+                <td>{block}</td>
+                <td>{time}</td>
+                <td>{result}</td>
+                <td><code>{}</code></td>
+                <td style="color:{color};">{status}</td>
+                <td>{report_link}{evidence_link}</td>
+            </tr>"#,
+            encode_text(transaction_id)
+        )
+    }).collect::<Vec<_>>().join("\n");
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+    <head>
+        <meta charset="utf-8">
+        <title>Proof of Reserves</title>
+    </head>
+    <body>
+        <h2>Proof of Reserves</h2>
+        <p><a href="feed.atom">Atom feed</a></p>
+        <h3>Latest Attestation</h3>
+        {latest}
+        <h3>History</h3>
+        {chart}
+        <table border="1" cellpadding="4" style="border-collapse:collapse;">
+            <tr><th>Block</th><th>Time</th><th>Result</th><th>Transaction</th><th>Verification</th><th>Downloads</th></tr>
+            {rows}
+        </table>
+        <p style="color:gray;">Reports are signed by the prover over their canonical JSON. Evidence bundles can be checked
+        offline with <code>reserve-tracker verify-bundle</code>. Generated {generated}.</p>
+    </body>
+</html>"#,
+        chart = history_chart(&reports, breakdowns),
+        generated = date(generated),
+    )
+}
+
+/// An Atom feed with an entry per accepted attestation, newest first. Pending and rejected attestations
+/// are left out, and an accepted one only appears once its report is signed, so that each entry keeps
+/// the date of its block. Links are relative to the feed, so it works from the server and from a static
+/// export alike.
+pub fn render_atom_feed(
+    history: &[Attestation],
+    reports: &HashMap<String, SignedReport>,
+    evidence: Option<&HashSet<String>>,
+    generated: i64,
+) -> String {
+    let rfc3339 = |timestamp: i64| {
+        chrono::DateTime::from_timestamp(timestamp, 0).unwrap_or_default().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+    };
+    // The feed is identified by the prover, which signs every report.
+    let feed_id = match reports.values().next() {
+        Some(signed) => format!("urn:aleo:{}:attestations", signed.signer),
+        None => "urn:aleo:attestations".to_string(),
+    };
+    let updated = signed(history).last().map_or(generated, |report| report.timestamp);
+
+    let entries = signed(history).into_iter().rev().map(|report| {
+        let txid = encode_double_quoted_attribute(&report.transaction_id);
+        let evidence_link = match evidence.is_none_or(|evidence| evidence.contains(&report.transaction_id)) {
+            true => format!(r#"
+        <link rel="related" type="application/json" title="Evidence bundle" href="evidence/{txid}.json"/>"#),
+            false => String::new(),
+        };
+        let summary = summary(report);
+        format!(
+            r#"    <entry>
+        <id>urn:aleo:transaction:{txid}</id>
+        <title>Attestation at block {height}: {title}</title>
+        <updated>{updated}</updated>
+        <link rel="alternate" type="application/json" title="Signed report" href="reports/{txid}.json"/>{evidence_link}
+        <content type="text">Height: {height}
+Timestamp: {timestamp}
+Result: {title}
+Data hash: {data_hash}
+Transaction: {txid}</content>
+    </entry>"#,
+            height = report.block_height,
+            title = encode_text(&summary),
+            updated = rfc3339(report.timestamp),
+            timestamp = date(report.timestamp),
+            data_hash = encode_text(&report.data_hash),
+        )
+    }).collect::<Vec<_>>().join("\n");
+
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+    <id>{}</id>
+    <title>Proof of Reserves Attestations</title>
+    <updated>{}</updated>
+    <author><name>reserve-tracker</name></author>
+    <link rel="self" href="feed.atom"/>
+{}
+</feed>
+"#,
+        encode_text(&feed_id), rfc3339(updated), entries
+    )
+}
+
+/// Writes the attestation history to a directory of static files that can be served from object storage
+/// or a CDN: `index.html`, `history.json`, `feed.atom`, a signed report per attestation under `reports/`, a chart per
+/// asset and portfolio under `charts/`, and the evidence bundles under `evidence/`. Bundles already in the directory
+/// are kept; missing ones are fetched only if an endpoint and the prover's key are given.
+pub fn export_site(
+    out: &Path,
+    transactions: &[TrackedTransaction],
+    reports: &HashMap<String, SignedReport>,
+    verifications: &HashMap<String, Verification>,
+    breakdowns: &HashMap<String, BTreeMap<String, u128>>,
+    fetch: Option<(&str, &str)>,
+    network: &str,
+) -> Result<(), String> {
+    let write = |path: &Path, contents: &str| {
+        std::fs::write(path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    };
+    for dir in ["reports", "evidence", "charts"] {
+        std::fs::create_dir_all(out.join(dir))
+            .map_err(|e| format!("Failed to create {}: {}", out.join(dir).display(), e))?;
+    }
+    let history = history(transactions, reports);
+
+    let mut evidence = HashSet::new();
+    for attestation in history.iter().filter(|attestation| attestation.accepted()) {
+        let txid = attestation.transaction_id();
+        let path = out.join("evidence").join(format!("{}.json", txid));
+        if !path.exists() {
+            let Some((endpoint, private_key)) = fetch else { continue };
+            match export_bundle(txid, private_key, endpoint, network) {
+                Ok(bundle) => write(&path, &serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())?)?,
+                Err(e) => {
+                    warn!("Leaving out the evidence bundle for {}: {}", txid, e);
+                    continue;
+                }
+            }
+        }
+        evidence.insert(txid.to_string());
+    }
+
+    for signed in reports.values() {
+        let path = out.join("reports").join(format!("{}.json", signed.report.transaction_id));
+        write(&path, &serde_json::to_string_pretty(signed).map_err(|e| e.to_string())?)?;
+    }
+    for (name, svg) in history_charts(&signed(&history), breakdowns) {
+        let name = name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '-' }).collect::<String>();
+        let name = name.trim_end_matches('-');
+        write(&out.join("charts").join(format!("{}.svg", name)), &svg)?;
+    }
+
+    let generated = chrono::Utc::now().timestamp();
+    let attestations = history.iter().map(|attestation| json!({
+        "transaction_id": attestation.transaction_id(),
+        "status": attestation.status(),
+        "signed_report": reports.get(attestation.transaction_id()),
+        "verification": verifications.get(attestation.transaction_id()),
+        "evidence": evidence.contains(attestation.transaction_id()),
+    })).collect::<Vec<_>>();
+    let history_json = json!({ "generated": generated, "attestations": attestations });
+    write(&out.join("history.json"), &serde_json::to_string_pretty(&history_json).map_err(|e| e.to_string())?)?;
+    write(&out.join("feed.atom"), &render_atom_feed(&history, reports, Some(&evidence), generated))?;
+    write(&out.join("index.html"), &render_public_page(&history, verifications, breakdowns, Some(&evidence), generated))?;
+
+    info!("Exported {} attestations, {} with evidence bundles, to {}", history.len(), evidence.len(), out.display());
+    Ok(())
+}
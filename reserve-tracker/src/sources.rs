@@ -0,0 +1,326 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use snarkvm::prelude::{Field, FromBits, Network, SizeInDataBits, ToBits};
+
+use crate::utilities::{find_mapping_value, HTTP_AGENT};
+use crate::CurrentNetwork;
+
+/// A source of balances for a set of addresses on some chain.
+pub trait BalanceSource: Send + Sync {
+    /// The asset the balances are denominated in, e.g. `BTC` or `ETH`.
+    fn asset(&self) -> &str;
+
+    /// The addresses whose balances are summed.
+    fn addresses(&self) -> &[String];
+
+    /// The total balance of the addresses, in the smallest unit of the asset.
+    fn total_balance(&self) -> Result<u128, String>;
+}
+
+/// Reads public credits balances from the `account` mapping of `credits.aleo`.
+pub struct CreditsSource {
+    pub endpoint: String,
+    pub network: String,
+    pub addresses: Vec<String>,
+}
+
+impl CreditsSource {
+    /// The public balance of one address, in microcredits. An address without an entry has none.
+    pub fn balance(&self, address: &str) -> Result<u64, String> {
+        let value = find_mapping_value("credits.aleo", "account", address, &self.endpoint, &self.network)
+            .map_err(|e| format!("Failed to read balance of {}: {}", address, e))?;
+        match value {
+            Some(value) => value.trim_end_matches("u64").parse()
+                .map_err(|e| format!("Failed to parse balance of {} '{}': {}", address, value, e)),
+            None => Ok(0),
+        }
+    }
+}
+
+impl BalanceSource for CreditsSource {
+    fn asset(&self) -> &str {
+        "credits"
+    }
+
+    fn addresses(&self) -> &[String] {
+        &self.addresses
+    }
+
+    fn total_balance(&self) -> Result<u128, String> {
+        self.addresses.iter().try_fold(0u128, |total, address| Ok(total + self.balance(address)? as u128))
+    }
+}
+
+/// Reads balances from a Bitcoin Core-style JSON-RPC node, using `scantxoutset`.
+pub struct BitcoinCoreSource {
+    pub url: String,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub addresses: Vec<String>,
+}
+
+impl BalanceSource for BitcoinCoreSource {
+    fn asset(&self) -> &str {
+        "BTC"
+    }
+
+    fn addresses(&self) -> &[String] {
+        &self.addresses
+    }
+
+    fn total_balance(&self) -> Result<u128, String> {
+        let descriptors = self.addresses.iter()
+            .map(|address| serde_json::json!({ "desc": format!("addr({})", address) }))
+            .collect::<Vec<_>>();
+        let mut request = HTTP_AGENT.post(&self.url);
+        if let Some(user) = &self.user {
+            let credentials = format!("{}:{}", user, self.password.as_deref().unwrap_or_default());
+            let encoded = base64::engine::general_purpose::STANDARD.encode(credentials);
+            request = request.set("Authorization", &format!("Basic {}", encoded));
+        }
+        let result = json_rpc(request, "scantxoutset", serde_json::json!(["start", descriptors]))?;
+        // The total is in BTC. Any satoshi amount up to the supply cap is exact in an f64.
+        let total = result.get("total_amount")
+            .and_then(|total| total.as_f64())
+            .ok_or_else(|| "Missing total_amount in scantxoutset result".to_string())?;
+        Ok((total * 100_000_000.0).round() as u128)
+    }
+}
+
+/// Reads balances from an Ethereum JSON-RPC node, using `eth_getBalance`.
+pub struct EthereumRpcSource {
+    pub url: String,
+    pub addresses: Vec<String>,
+}
+
+impl BalanceSource for EthereumRpcSource {
+    fn asset(&self) -> &str {
+        "ETH"
+    }
+
+    fn addresses(&self) -> &[String] {
+        &self.addresses
+    }
+
+    fn total_balance(&self) -> Result<u128, String> {
+        self.addresses.iter().try_fold(0u128, |total, address| {
+            let result = json_rpc(HTTP_AGENT.post(&self.url), "eth_getBalance", serde_json::json!([address, "latest"]))?;
+            let wei = result.as_str()
+                .ok_or_else(|| format!("Unexpected eth_getBalance result for {}: {}", address, result))?;
+            let wei = u128::from_str_radix(wei.trim_start_matches("0x"), 16)
+                .map_err(|e| format!("Failed to parse balance of {}: {}", address, e))?;
+            total.checked_add(wei).ok_or_else(|| "ETH total overflows u128".to_string())
+        })
+    }
+}
+
+/// The configuration of an off-Aleo balance source, as read from the sources file.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum SourceConfig {
+    Bitcoin {
+        url: String,
+        user: Option<String>,
+        password: Option<String>,
+        addresses: Vec<String>,
+    },
+    Ethereum {
+        url: String,
+        addresses: Vec<String>,
+    },
+}
+
+impl SourceConfig {
+    pub fn build(&self) -> Box<dyn BalanceSource> {
+        match self.clone() {
+            SourceConfig::Bitcoin { url, user, password, addresses } => {
+                Box::new(BitcoinCoreSource { url, user, password, addresses })
+            }
+            SourceConfig::Ethereum { url, addresses } => Box::new(EthereumRpcSource { url, addresses }),
+        }
+    }
+}
+
+/// The last total read from a balance source.
+#[derive(Clone, Debug)]
+pub struct SourceTotal {
+    pub asset: String,
+    pub addresses: usize,
+    pub total: Result<u128, String>,
+}
+
+/// Encodes an asset name, such as `BTC`, as a field element for the on-chain commitment.
+pub fn asset_field(asset: &str) -> Result<Field<CurrentNetwork>, String> {
+    Field::from_bits_le(&asset.as_bytes().to_bits_le())
+        .map_err(|e| format!("Failed to encode asset '{}': {}", asset, e))
+}
+
+/// Decodes an asset name from its field element encoding.
+pub fn asset_name(field: &Field<CurrentNetwork>) -> String {
+    let bytes = field.to_bits_le()
+        .chunks(8)
+        .map(|bits| bits.iter().rev().fold(0u8, |byte, bit| (byte << 1) | *bit as u8))
+        .take_while(|byte| *byte != 0)
+        .collect::<Vec<_>>();
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+/// Hashes the address list of a source, so the commitment is bound to the addresses it covers.
+pub fn hash_addresses(addresses: &[String]) -> Result<Field<CurrentNetwork>, String> {
+    let digest = CurrentNetwork::hash_sha3_256(&addresses.join(",").as_bytes().to_bits_le())
+        .map_err(|e| format!("Failed to hash addresses: {}", e))?;
+    Field::from_bits_le(&digest[..Field::<CurrentNetwork>::size_in_data_bits()])
+        .map_err(|e| format!("Failed to hash addresses: {}", e))
+}
+
+fn json_rpc(request: ureq::Request, method: &str, params: serde_json::Value) -> Result<serde_json::Value, String> {
+    let response = request
+        .send_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+        .map_err(|e| format!("Failed to call {}: {}", method, e))?
+        .into_json::<serde_json::Value>()
+        .map_err(|e| format!("Failed to parse {} response: {}", method, e))?;
+    match response.get("error") {
+        Some(error) if !error.is_null() => Err(format!("{} returned an error: {}", method, error)),
+        _ => response.get("result").cloned().ok_or_else(|| format!("{} returned no result", method)),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    /// A stand-in JSON-RPC node: answers each request with the reply to its body, and sends the
+    /// request headers and body back to the test.
+    fn stand_in(reply: fn(&serde_json::Value) -> serde_json::Value) -> (String, mpsc::Receiver<(String, serde_json::Value)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut headers = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    headers.push_str(&line);
+                }
+                let length = headers.lines()
+                    .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|n| n.trim().parse::<usize>().unwrap()))
+                    .unwrap_or(0);
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let request = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+                let response = reply(&request).to_string();
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(), response
+                ).unwrap();
+                let _ = tx.send((headers, request));
+            }
+        });
+        (url, rx)
+    }
+
+    #[test]
+    fn bitcoin_total_is_read_in_satoshis() {
+        let (url, requests) = stand_in(|_| serde_json::json!({ "result": { "total_amount": 1.23456789 }, "error": null, "id": 1 }));
+        let source = BitcoinCoreSource {
+            url,
+            user: Some("user".to_string()),
+            password: Some("pass".to_string()),
+            addresses: vec!["bc1qfirst".to_string(), "bc1qsecond".to_string()],
+        };
+
+        assert_eq!(source.total_balance(), Ok(123_456_789));
+        let (headers, request) = requests.recv().unwrap();
+        assert!(headers.contains("Basic dXNlcjpwYXNz"));
+        assert_eq!(request["method"], "scantxoutset");
+        assert_eq!(request["params"], serde_json::json!(["start", [{ "desc": "addr(bc1qfirst)" }, { "desc": "addr(bc1qsecond)" }]]));
+    }
+
+    #[test]
+    fn ethereum_balances_are_summed() {
+        let (url, requests) = stand_in(|request| {
+            let balance = match request["params"][0].as_str() {
+                Some("0xfirst") => "0xde0b6b3a7640000",
+                _ => "0x1",
+            };
+            serde_json::json!({ "result": balance, "id": 1 })
+        });
+        let source = EthereumRpcSource { url, addresses: vec!["0xfirst".to_string(), "0xsecond".to_string()] };
+
+        assert_eq!(source.total_balance(), Ok(1_000_000_000_000_000_001));
+        let methods = requests.iter().take(2).map(|(_, request)| request["method"].clone()).collect::<Vec<_>>();
+        assert_eq!(methods, ["eth_getBalance", "eth_getBalance"]);
+    }
+
+    #[test]
+    fn rpc_errors_are_reported() {
+        let (url, _requests) = stand_in(|_| serde_json::json!({ "result": null, "error": { "code": -8, "message": "Scan already in progress" }, "id": 1 }));
+        let source = BitcoinCoreSource { url, user: None, password: None, addresses: vec!["bc1qfirst".to_string()] };
+
+        let error = source.total_balance().unwrap_err();
+        assert!(error.contains("scantxoutset returned an error"), "{}", error);
+    }
+
+    /// A stand-in Aleo endpoint: answers each GET with the reply to its path.
+    fn endpoint_stand_in(reply: fn(&str) -> serde_json::Value) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+                let response = reply(path).to_string();
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(), response
+                ).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn credits_balances_are_summed() {
+        let endpoint = endpoint_stand_in(|path| match path.rsplit('/').next() {
+            Some("aleo1first") => serde_json::json!("5000000u64"),
+            Some("aleo1second") => serde_json::json!("7u64"),
+            _ => serde_json::Value::Null,
+        });
+        let source = CreditsSource {
+            endpoint,
+            network: "testnet".to_string(),
+            addresses: vec!["aleo1first".to_string(), "aleo1second".to_string(), "aleo1empty".to_string()],
+        };
+
+        assert_eq!(source.asset(), "credits");
+        assert_eq!(source.balance("aleo1empty"), Ok(0));
+        assert_eq!(source.total_balance(), Ok(5_000_007));
+    }
+
+    #[test]
+    fn asset_names_round_trip() {
+        assert_eq!(asset_name(&asset_field("BTC").unwrap()), "BTC");
+    }
+}
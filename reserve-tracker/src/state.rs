@@ -1,22 +1,252 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use snarkvm::prelude::VM;
 use snarkvm::ledger::store::helpers::memory::ConsensusMemory;
 use tokio::sync::mpsc;
 
 use crate::CurrentNetwork;
+use crate::alerts::{AlertRule, AlertState};
 use crate::background::BackgroundTaskMsg;
+use crate::config::Portfolio;
+use crate::liabilities::LiabilityTree;
+use crate::prices::{PriceFeed, RecordedPrice};
+use crate::records::RecordStore;
+use crate::reports::SignedReport;
+use crate::evidence::Verification;
+use crate::sources::{SourceConfig, SourceTotal};
+use crate::webhooks::{Delivery, WebhookConfig};
 
 use once_cell::sync::OnceCell;
-use std::sync::Mutex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 
 pub static VM_GLOBAL: OnceCell<Mutex<VM<CurrentNetwork, ConsensusMemory<CurrentNetwork>>>> = OnceCell::new();
 
-pub struct AppState {
+/// What each attestation publishes on-chain.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Publish the exact reserve total.
+    Reserves,
+    /// Publish only whether the reserves cover a threshold. The addresses are still public
+    /// arguments of the attestation, so the total can be recomputed from their balances.
+    Solvency,
+}
+
+/// What a tracked attestation transaction attests to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionKind {
+    /// The public credits balances, or whether they cover the solvency threshold.
+    Reserves,
+    /// The public balances of one token asset.
+    Token,
+    /// The committed total of an off-Aleo source.
+    External,
+    /// The prover's private records.
+    Private,
+}
+
+/// A tracked attestation transaction.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(from = "StoredTransaction")]
+pub struct TrackedTransaction {
+    pub id: String,
+    pub kind: TransactionKind,
+    /// How the network resolved it, once known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<Resolution>,
+}
+
+/// Whether a tracked attestation was accepted. Timed out attestations count as rejected, as in the webhooks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    Accepted,
+    Rejected,
+}
+
+impl TransactionKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TransactionKind::Reserves => "Reserves",
+            TransactionKind::Token => "Token",
+            TransactionKind::External => "External",
+            TransactionKind::Private => "Private records",
+        }
+    }
+}
+
+impl TrackedTransaction {
+    pub fn new(id: impl ToString, kind: TransactionKind) -> Self {
+        Self { id: id.to_string(), kind, resolution: None }
+    }
+}
+
+/// Earlier versions stored only the IDs, which were all reserve attestations.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredTransaction {
+    Id(String),
+    Tracked {
+        id: String,
+        kind: TransactionKind,
+        #[serde(default)]
+        resolution: Option<Resolution>,
+    },
+}
+
+impl From<StoredTransaction> for TrackedTransaction {
+    fn from(stored: StoredTransaction) -> Self {
+        match stored {
+            StoredTransaction::Id(id) => Self::new(id, TransactionKind::Reserves),
+            StoredTransaction::Tracked { id, kind, resolution } => Self { resolution, ..Self::new(id, kind) },
+        }
+    }
+}
+
+/// A liabilities commitment transaction, with the reserves attestation broadcast in the same run.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(from = "StoredLiabilityTransaction")]
+pub struct LiabilityTransaction {
+    pub id: String,
+    pub reserves: Option<String>,
+}
+
+/// Earlier versions stored only the IDs of the liabilities commitments.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredLiabilityTransaction {
+    Id(String),
+    Linked { id: String, reserves: Option<String> },
+}
+
+impl From<StoredLiabilityTransaction> for LiabilityTransaction {
+    fn from(stored: StoredLiabilityTransaction) -> Self {
+        match stored {
+            StoredLiabilityTransaction::Id(id) => Self { id, reserves: None },
+            StoredLiabilityTransaction::Linked { id, reserves } => Self { id, reserves },
+        }
+    }
+}
+
+/// A broadcast transaction that has not been confirmed yet, kept across restarts.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PendingTransaction {
+    pub id: String,
+    /// The root function, e.g. `proof_of_reserves_v0_2_0.aleo/record_liabilities`.
+    pub function: String,
+    /// The addresses the attestation covers, for its report.
     pub addresses: Vec<String>,
+    /// Unix time of the broadcast.
+    pub broadcast: i64,
+}
+
+/// Where a run requested through the API is.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// A run requested through the API. Queued jobs are taken up by the next run, whether it was
+/// triggered or scheduled.
+#[derive(Clone, Debug, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub status: JobStatus,
+    /// Unix times of the request, and of the start and end of the run.
+    pub requested: i64,
+    pub started: Option<i64>,
+    pub finished: Option<i64>,
+    /// The ID the run logs under.
+    pub run_id: Option<String>,
+    pub error: Option<String>,
+    /// The attestation transactions the run broadcast.
     pub transactions: Vec<String>,
+}
+
+/// How the last attestation run ended.
+#[derive(Clone, Debug)]
+pub struct RunOutcome {
+    pub finished: DateTime<Utc>,
+    pub error: Option<String>,
+}
+
+pub struct AppState {
+    pub addresses: Vec<String>,
+    pub transactions: Vec<TrackedTransaction>,
     pub task_tx: mpsc::Sender<BackgroundTaskMsg>,
     pub private_key: String,
     pub endpoint: String,
+    pub endpoints: Vec<String>,
+    /// Whether to check the block of each attestation against every endpoint.
+    pub light_verification: bool,
+    /// How many endpoints must return the block for the inclusion check; all of them if unset.
+    pub inclusion_quorum: Option<usize>,
+    /// Priority fee paid on each attestation, in microcredits.
+    pub priority_fee: u64,
     pub transactions_file: String,
+    pub liabilities: Option<LiabilityTree>,
+    pub liability_transactions: Vec<LiabilityTransaction>,
+    pub liability_transactions_file: String,
+    pub mode: Mode,
+    pub solvency_threshold: Option<u64>,
+    pub include_stake: bool,
+    pub assets: Vec<String>,
+    pub sources: Vec<SourceConfig>,
+    pub source_totals: Vec<SourceTotal>,
+    pub view_keys: Vec<String>,
+    pub records: RecordStore,
+    pub records_file: String,
+    pub price_feed: Option<Arc<dyn PriceFeed>>,
+    pub currency: String,
+    /// The prices recorded for each attestation on acceptance, by transaction ID.
+    pub prices: HashMap<String, Vec<RecordedPrice>>,
+    pub prices_file: String,
+    pub started: DateTime<Utc>,
+    pub last_run: Option<RunOutcome>,
+    pub last_success: Option<DateTime<Utc>>,
+    /// How old the last successful attestation may be before readiness fails, in seconds.
+    pub max_attestation_age: u64,
+    pub webhooks: Vec<WebhookConfig>,
+    /// Webhook deliveries that have not succeeded yet.
+    pub outbox: Vec<Delivery>,
+    pub outbox_file: String,
+    pub pending: Vec<PendingTransaction>,
+    pub pending_file: String,
+    pub alert_rules: Vec<AlertRule>,
+    pub alerts: AlertState,
+    pub alerts_file: String,
+    /// The signed report of each accepted attestation, by transaction ID.
+    pub reports: HashMap<String, SignedReport>,
+    pub reports_file: String,
+    /// The local verification of each fetched attestation, by transaction ID.
+    pub verifications: HashMap<String, Verification>,
+    pub verifications_file: String,
+    /// The portfolios from the config file, which tag the addresses in the history charts.
+    pub portfolios: Vec<Portfolio>,
+    /// The credits total of each portfolio at each accepted attestation, by transaction ID.
+    pub breakdowns: HashMap<String, BTreeMap<String, u128>>,
+    pub breakdowns_file: String,
+    /// The transaction IDs of the stored evidence bundles, which the public site serves.
+    pub evidence: HashSet<String>,
+    pub evidence_dir: String,
+    /// Seconds between scheduled attestations.
+    pub cadence: u64,
+    /// The runs requested through the API, oldest first.
+    pub jobs: Vec<Job>,
+}
+
+impl AppState {
+    /// Whether balances, per-portfolio totals and valuations are kept off every page and the API,
+    /// so that solvency mode publishes no more than whether the threshold is covered.
+    pub fn hides_totals(&self) -> bool {
+        self.mode == Mode::Solvency
+    }
 }
 
 
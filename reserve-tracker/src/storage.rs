@@ -1,5 +1,15 @@
-use serde_json;
+use serde::{de::DeserializeOwned, Serialize};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::liabilities::{CustomerBalance, LiabilityCommitment};
+use crate::prices::{PricePoint, RecordedPrice, StoredPrices};
+use crate::records::RecordStore;
+use crate::sources::SourceConfig;
+use crate::webhooks::Delivery;
+use crate::alerts::AlertState;
+use crate::reports::SignedReport;
+use crate::evidence::{EvidenceBundle, Verification};
+use crate::state::PendingTransaction;
 
 pub async fn save_addresses(addresses: &[String], file: &str) -> Result<(), std::io::Error> {
     let serialized = serde_json::to_string(&addresses)?;
@@ -20,14 +30,33 @@ pub async fn load_addresses(file: &str) -> Result<Vec<String>, std::io::Error> {
     }
 }
 
-pub async fn save_transactions(transactions: &[String], file: &str) -> Result<(), std::io::Error> {
+pub async fn save_assets(assets: &[String], file: &str) -> Result<(), std::io::Error> {
+    let serialized = serde_json::to_string(assets)?;
+    let mut f = tokio::fs::File::create(file).await?;
+    f.write_all(serialized.as_bytes()).await?;
+    Ok(())
+}
+
+pub async fn load_assets(file: &str) -> Result<Vec<String>, std::io::Error> {
+    match tokio::fs::File::open(file).await {
+        Ok(mut f) => {
+            let mut contents = String::new();
+            f.read_to_string(&mut contents).await?;
+            let assets = serde_json::from_str(&contents)?;
+            Ok(assets)
+        }
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+pub async fn save_transactions<T: Serialize>(transactions: &[T], file: &str) -> Result<(), std::io::Error> {
     let serialized = serde_json::to_string(transactions)?;
     let mut f = tokio::fs::File::create(file).await?;
     f.write_all(serialized.as_bytes()).await?;
     Ok(())
 }
 
-pub async fn load_transactions(file: &str) -> Result<Vec<String>, std::io::Error> {
+pub async fn load_transactions<T: DeserializeOwned>(file: &str) -> Result<Vec<T>, std::io::Error> {
     match tokio::fs::File::open(file).await {
         Ok(mut f) => {
             let mut contents = String::new();
@@ -38,3 +67,213 @@ pub async fn load_transactions(file: &str) -> Result<Vec<String>, std::io::Error
         Err(_) => Ok(Vec::new()),
     }
 }
+
+pub async fn load_customer_balances(file: &str) -> Result<Vec<CustomerBalance>, std::io::Error> {
+    let mut f = tokio::fs::File::open(file).await?;
+    let mut contents = String::new();
+    f.read_to_string(&mut contents).await?;
+    let balances = serde_json::from_str(&contents)?;
+    Ok(balances)
+}
+
+pub async fn save_liability_commitment(commitment: &LiabilityCommitment, file: &str) -> Result<(), std::io::Error> {
+    let serialized = serde_json::to_string(commitment)?;
+    let mut f = tokio::fs::File::create(file).await?;
+    f.write_all(serialized.as_bytes()).await?;
+    Ok(())
+}
+
+pub async fn save_records(records: &RecordStore, file: &str) -> Result<(), std::io::Error> {
+    let serialized = serde_json::to_string(records)?;
+    let mut f = tokio::fs::File::create(file).await?;
+    f.write_all(serialized.as_bytes()).await?;
+    Ok(())
+}
+
+pub async fn load_records(file: &str) -> Result<RecordStore, std::io::Error> {
+    match tokio::fs::File::open(file).await {
+        Ok(mut f) => {
+            let mut contents = String::new();
+            f.read_to_string(&mut contents).await?;
+            let records = serde_json::from_str(&contents)?;
+            Ok(records)
+        }
+        Err(_) => Ok(RecordStore::default()),
+    }
+}
+
+pub async fn load_sources(file: &str) -> Result<Vec<SourceConfig>, std::io::Error> {
+    let mut f = tokio::fs::File::open(file).await?;
+    let mut contents = String::new();
+    f.read_to_string(&mut contents).await?;
+    let sources = serde_json::from_str(&contents)?;
+    Ok(sources)
+}
+
+pub async fn load_static_prices(file: &str) -> Result<HashMap<String, Vec<PricePoint>>, std::io::Error> {
+    let mut f = tokio::fs::File::open(file).await?;
+    let mut contents = String::new();
+    f.read_to_string(&mut contents).await?;
+    let prices = serde_json::from_str(&contents)?;
+    Ok(prices)
+}
+
+pub async fn save_prices(prices: &HashMap<String, Vec<RecordedPrice>>, file: &str) -> Result<(), std::io::Error> {
+    let serialized = serde_json::to_string(prices)?;
+    let mut f = tokio::fs::File::create(file).await?;
+    f.write_all(serialized.as_bytes()).await?;
+    Ok(())
+}
+
+pub async fn load_prices(file: &str) -> Result<HashMap<String, Vec<RecordedPrice>>, std::io::Error> {
+    match tokio::fs::File::open(file).await {
+        Ok(mut f) => {
+            let mut contents = String::new();
+            f.read_to_string(&mut contents).await?;
+            let prices: HashMap<String, StoredPrices> = serde_json::from_str(&contents)?;
+            Ok(prices.into_iter().map(|(txid, prices)| (txid, prices.into())).collect())
+        }
+        Err(_) => Ok(HashMap::new()),
+    }
+}
+
+pub async fn save_outbox(outbox: &[Delivery], file: &str) -> Result<(), std::io::Error> {
+    let serialized = serde_json::to_string(outbox)?;
+    let mut f = tokio::fs::File::create(file).await?;
+    f.write_all(serialized.as_bytes()).await?;
+    Ok(())
+}
+
+pub async fn load_outbox(file: &str) -> Result<Vec<Delivery>, std::io::Error> {
+    match tokio::fs::File::open(file).await {
+        Ok(mut f) => {
+            let mut contents = String::new();
+            f.read_to_string(&mut contents).await?;
+            let outbox = serde_json::from_str(&contents)?;
+            Ok(outbox)
+        }
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+pub async fn save_pending(pending: &[PendingTransaction], file: &str) -> Result<(), std::io::Error> {
+    let serialized = serde_json::to_string(pending)?;
+    let mut f = tokio::fs::File::create(file).await?;
+    f.write_all(serialized.as_bytes()).await?;
+    Ok(())
+}
+
+pub async fn load_pending(file: &str) -> Result<Vec<PendingTransaction>, std::io::Error> {
+    match tokio::fs::File::open(file).await {
+        Ok(mut f) => {
+            let mut contents = String::new();
+            f.read_to_string(&mut contents).await?;
+            let pending = serde_json::from_str(&contents)?;
+            Ok(pending)
+        }
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+pub async fn save_alerts(alerts: &AlertState, file: &str) -> Result<(), std::io::Error> {
+    let serialized = serde_json::to_string(alerts)?;
+    let mut f = tokio::fs::File::create(file).await?;
+    f.write_all(serialized.as_bytes()).await?;
+    Ok(())
+}
+
+pub async fn load_alerts(file: &str) -> Result<AlertState, std::io::Error> {
+    match tokio::fs::File::open(file).await {
+        Ok(mut f) => {
+            let mut contents = String::new();
+            f.read_to_string(&mut contents).await?;
+            let alerts = serde_json::from_str(&contents)?;
+            Ok(alerts)
+        }
+        Err(_) => Ok(AlertState::default()),
+    }
+}
+
+pub async fn save_reports(reports: &HashMap<String, SignedReport>, file: &str) -> Result<(), std::io::Error> {
+    let serialized = serde_json::to_string(reports)?;
+    let mut f = tokio::fs::File::create(file).await?;
+    f.write_all(serialized.as_bytes()).await?;
+    Ok(())
+}
+
+pub async fn load_reports(file: &str) -> Result<HashMap<String, SignedReport>, std::io::Error> {
+    match tokio::fs::File::open(file).await {
+        Ok(mut f) => {
+            let mut contents = String::new();
+            f.read_to_string(&mut contents).await?;
+            let reports = serde_json::from_str(&contents)?;
+            Ok(reports)
+        }
+        Err(_) => Ok(HashMap::new()),
+    }
+}
+
+pub async fn save_verifications(verifications: &HashMap<String, Verification>, file: &str) -> Result<(), std::io::Error> {
+    let serialized = serde_json::to_string(verifications)?;
+    let mut f = tokio::fs::File::create(file).await?;
+    f.write_all(serialized.as_bytes()).await?;
+    Ok(())
+}
+
+pub async fn load_verifications(file: &str) -> Result<HashMap<String, Verification>, std::io::Error> {
+    match tokio::fs::File::open(file).await {
+        Ok(mut f) => {
+            let mut contents = String::new();
+            f.read_to_string(&mut contents).await?;
+            let verifications = serde_json::from_str(&contents)?;
+            Ok(verifications)
+        }
+        Err(_) => Ok(HashMap::new()),
+    }
+}
+
+/// Stores an evidence bundle as `<transaction ID>.json` in the evidence directory.
+pub async fn save_bundle(bundle: &EvidenceBundle, dir: &str) -> Result<(), std::io::Error> {
+    let serialized = serde_json::to_string(bundle)?;
+    let path = std::path::Path::new(dir).join(format!("{}.json", bundle.transaction_id));
+    let mut f = tokio::fs::File::create(path).await?;
+    f.write_all(serialized.as_bytes()).await?;
+    Ok(())
+}
+
+/// Reads a stored evidence bundle as the JSON it was written as.
+pub async fn load_bundle(txid: &str, dir: &str) -> Result<String, std::io::Error> {
+    tokio::fs::read_to_string(std::path::Path::new(dir).join(format!("{}.json", txid))).await
+}
+
+/// The transaction IDs of the stored evidence bundles, creating the evidence directory if missing.
+pub async fn load_bundle_ids(dir: &str) -> Result<HashSet<String>, std::io::Error> {
+    tokio::fs::create_dir_all(dir).await?;
+    let mut ids = HashSet::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if let Some(txid) = entry.file_name().to_str().and_then(|name| name.strip_suffix(".json")) {
+            ids.insert(txid.to_string());
+        }
+    }
+    Ok(ids)
+}
+
+pub async fn save_breakdowns(breakdowns: &HashMap<String, BTreeMap<String, u128>>, file: &str) -> Result<(), std::io::Error> {
+    let serialized = serde_json::to_string(breakdowns)?;
+    let mut f = tokio::fs::File::create(file).await?;
+    f.write_all(serialized.as_bytes()).await?;
+    Ok(())
+}
+
+pub async fn load_breakdowns(file: &str) -> Result<HashMap<String, BTreeMap<String, u128>>, std::io::Error> {
+    match tokio::fs::File::open(file).await {
+        Ok(mut f) => {
+            let mut contents = String::new();
+            f.read_to_string(&mut contents).await?;
+            let breakdowns = serde_json::from_str(&contents)?;
+            Ok(breakdowns)
+        }
+        Err(_) => Ok(HashMap::new()),
+    }
+}
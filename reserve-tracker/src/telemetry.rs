@@ -0,0 +1,74 @@
+use tracing_subscriber::{fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+/// How log lines are written to stdout.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    /// Human-readable lines.
+    Text,
+    /// One JSON object per line, with the fields of every enclosing span.
+    Json,
+}
+
+/// Installs the global tracing subscriber. Span close events are logged, so each stage of a run
+/// reports how long it took. The level defaults to `info` and can be changed with `RUST_LOG`.
+pub fn init(format: LogFormat, otlp_endpoint: Option<String>) -> Result<(), String> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let fmt_layer = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .with_span_events(FmtSpan::CLOSE)
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_span_list(true)
+            .with_span_events(FmtSpan::CLOSE)
+            .boxed(),
+    };
+    let registry = tracing_subscriber::registry().with(filter).with(fmt_layer);
+
+    if let Some(endpoint) = otlp_endpoint {
+        #[cfg(feature = "otlp")]
+        return registry
+            .with(otlp_layer(&endpoint)?)
+            .try_init()
+            .map_err(|e| format!("Failed to install tracing subscriber: {}", e));
+        #[cfg(not(feature = "otlp"))]
+        return Err(format!("Cannot export to {}; rebuild with `--features otlp`", endpoint));
+    }
+
+    registry.try_init().map_err(|e| format!("Failed to install tracing subscriber: {}", e))
+}
+
+/// Exports spans to an OpenTelemetry collector at the endpoint, over OTLP/gRPC.
+#[cfg(feature = "otlp")]
+fn otlp_layer<S>(endpoint: &str) -> Result<impl Layer<S>, String>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|e| format!("Failed to build OTLP exporter: {}", e))?;
+    let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_resource(opentelemetry_sdk::Resource::new([opentelemetry::KeyValue::new("service.name", "reserve-tracker")]))
+        .build();
+    let tracer = provider.tracer("reserve-tracker");
+    opentelemetry::global::set_tracer_provider(provider);
+
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Flushes any spans still buffered for the OTLP exporter.
+pub fn shutdown() {
+    #[cfg(feature = "otlp")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// A random identifier for an attestation run, carried by its root span.
+pub fn run_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
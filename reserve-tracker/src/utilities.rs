@@ -1,4 +1,26 @@
-use snarkvm::prelude::{Result, Network, Transaction, anyhow, ConfirmedTransaction};
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tracing::debug;
+use snarkvm::prelude::{Result, Network, Transaction, anyhow, Block, ConfirmedTransaction, Output, Argument, Address, Field, Identifier, Literal, Plaintext, PrivateKey, Zero};
+use crate::metrics::{timed_call, timed_send_json, METRICS};
+use crate::sources::asset_name;
+use crate::{CurrentNetwork, LEGACY_PROGRAM_ID, PROGRAM_ID, SOLVENCY_PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+/// How long connecting to a balance source or price feed may take.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a balance source or price feed may take to answer once connected.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The agent the balances and prices are read with. A source that hangs would otherwise hold up
+/// the attestation run, and with it the confirmation of the ones before.
+pub static HTTP_AGENT: Lazy<ureq::Agent> = Lazy::new(|| {
+    ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .build()
+});
 
 /// A utility to broadcast a transaction.
 pub fn broadcast_transaction<N: Network>(
@@ -10,8 +32,8 @@ pub fn broadcast_transaction<N: Network>(
     let url = format!("{}/{}/transaction/broadcast", endpoint, network);
     // Broadcast the transaction.
     // The transaction should be JSON.
-    let response = ureq::post(&url)
-        .send_json(serde_json::to_value(transaction)?)
+    let body = serde_json::to_value(transaction)?;
+    let response = timed_send_json("/transaction/broadcast", ureq::post(&url), body)
         .map_err(|e| anyhow!("Failed to broadcast transaction: {}", e))?;
     // Get the transaction ID from the response.
     let tx_id = response.into_json::<N::TransactionID>()
@@ -21,7 +43,7 @@ pub fn broadcast_transaction<N: Network>(
 }
 
 /// A utility to get the block height from a transaction.
-pub fn get_block_height<N: Network>(
+pub fn get_block_height(
     tx_id: &str,
     endpoint: &str,
     network: &str,
@@ -30,8 +52,7 @@ pub fn get_block_height<N: Network>(
     let url = format!("{}/{}/find/blockHash/{}", endpoint, network, tx_id);
     // Get the block hash from the transaction.
     // The response should be JSON with a field `blockHash` that denotes the block hash.
-    let response = ureq::get(&url)
-        .call()
+    let response = timed_call("/find/blockHash", ureq::get(&url))
         .map_err(|e| anyhow!("Failed to get block hash: {}", e))?;
     // Get the block hash from the response.
     // Remove " from the block hash.
@@ -42,8 +63,7 @@ pub fn get_block_height<N: Network>(
     let url = format!("{}/{}/height/{}", endpoint, network, block_hash);
     // Get the block height from the block hash.
     // The response should be JSON with a field `height` that denotes the block height.
-    let response = ureq::get(&url)
-        .call()
+    let response = timed_call("/height", ureq::get(&url))
         .map_err(|e| anyhow!("Failed to get block height: {}", e))?;
     // Get the block height from the response.
     let height = response.into_json::<serde_json::Value>()
@@ -52,29 +72,27 @@ pub fn get_block_height<N: Network>(
     Ok(height)
 }
 
-/// A utility to get the block given a height.
-pub fn get_block_timestamp<N: Network>(
+/// A utility to get the unix timestamp of the block at a height.
+pub fn get_block_timestamp(
     height: &str,
     endpoint: &str,
     network: &str,
-) -> Result<String> {
+) -> Result<i64> {
     // Construct a URL to get the block given a height.
     let url = format!("{}/{}/block/{}", endpoint, network, height);
     // Get the block given a height.
     // The response should be JSON with the block object.
-    let response = ureq::get(&url)
-        .call()
+    let response = timed_call("/block", ureq::get(&url))
         .map_err(|e| anyhow!("Failed to get block: {}", e))?;
     // Get the block object from the response.
     let block = response.into_json::<serde_json::Value>()
         .map_err(|e| anyhow!("Failed to get block: {}", e))?;
     // Get the timestamp.
-    let timestamp = block.get("header").unwrap().get("metadata").unwrap().get("timestamp").unwrap().to_string().replace("\"", "");
-    let timestamp = timestamp.parse::<i64>().unwrap();
-    // Pretty print the timestamp.
-    let block = chrono::DateTime::from_timestamp(timestamp as i64, 0).unwrap().to_string();
+    let timestamp = block.pointer("/header/metadata/timestamp")
+        .and_then(|timestamp| timestamp.as_i64())
+        .ok_or_else(|| anyhow!("Block {} has no timestamp", height))?;
 
-    Ok(block)
+    Ok(timestamp)
 }
 
 /// A utility to get the confirmed transaction object.
@@ -87,8 +105,7 @@ pub fn get_confirmed_transaction<N: Network>(
     let url = format!("{}/{}/transaction/confirmed/{}", endpoint, network, tx_id);
     // Get the confirmed transaction object.
     // The response should be JSON with the confirmed transaction object.
-    let response = ureq::get(&url)
-        .call()
+    let response = timed_call("/transaction/confirmed", ureq::get(&url))
         .map_err(|e| anyhow!("Failed to get confirmed transaction: {}", e))?;
     // Get the confirmed transaction object from the response.
     let tx = response.into_json::<ConfirmedTransaction<N>>()
@@ -98,24 +115,374 @@ pub fn get_confirmed_transaction<N: Network>(
 }
 
 /// A utility to query a mapping value.
-pub fn get_mapping_value<N: Network>(
+pub fn get_mapping_value(
     program_id: &str,
     mapping_name: &str,
     key: &str,
     endpoint: &str,
     network: &str,
 ) -> Result<String> {
+    find_mapping_value(program_id, mapping_name, key, endpoint, network)?
+        .ok_or_else(|| anyhow!("Mapping value not found for key {}", key))
+}
+
+/// A utility to query a mapping value that may not be set.
+pub fn find_mapping_value(
+    program_id: &str,
+    mapping_name: &str,
+    key: &str,
+    endpoint: &str,
+    network: &str,
+) -> Result<Option<String>> {
     // Construct a URL to query a mapping value.
     let url = format!("{}/{}/program/{program_id}/mapping/{mapping_name}/{key}", endpoint, network);
-    println!("Querying mapping value: {}", url);
+    debug!("Querying mapping value: {}", url);
     // Query the mapping value.
-    // The response should be JSON with the mapping value.
-    let response = ureq::get(&url)
-        .call()
+    // The response should be JSON with the mapping value, or null if it is not set.
+    let response = timed_call("/program/mapping", HTTP_AGENT.get(&url))
         .map_err(|e| anyhow!("Failed to query mapping value: {}", e))?;
     // Get the mapping value from the response.
     let value = response.into_json::<serde_json::Value>()
-        .map_err(|e| anyhow!("Failed to query mapping value: {}", e))?.as_str().unwrap().to_string();
+        .map_err(|e| anyhow!("Failed to query mapping value: {}", e))?.as_str().map(|value| value.to_string());
 
     Ok(value)
 }
+
+/// What an attestation recorded on-chain.
+pub enum AttestationResult {
+    /// The exact total balance, from the `data` mapping, or the committed liabilities total.
+    Total(String),
+    /// The total of the spent-and-refreshed private records, from the `private_data` mapping.
+    PrivateTotal(String),
+    /// The total of a token asset, from the `token_data` mapping.
+    TokenTotal { token_id: String, total: String },
+    /// The committed asset and total of each off-Aleo source, from the `external_data` mapping.
+    ExternalTotals(Vec<(String, String)>),
+    /// The liquid, bonded and unbonding totals, from the `stake_data` mapping.
+    Breakdown { liquid: u64, bonded: u64, unbonding: u64 },
+    /// Whether the reserves covered the threshold, from the `solvency` mapping.
+    Solvency { threshold: String, solvent: bool },
+}
+
+impl AttestationResult {
+    /// The asset the attestation covers.
+    pub fn asset(&self) -> String {
+        match self {
+            AttestationResult::Total(_) | AttestationResult::Breakdown { .. } | AttestationResult::Solvency { .. } => {
+                "credits".to_string()
+            }
+            AttestationResult::PrivateTotal(_) => "private credits".to_string(),
+            AttestationResult::TokenTotal { token_id, .. } => token_id.clone(),
+            AttestationResult::ExternalTotals(totals) => {
+                totals.iter().map(|(asset, _)| asset.as_str()).collect::<BTreeSet<_>>().into_iter().collect::<Vec<_>>().join(",")
+            }
+        }
+    }
+
+    /// The attested total in the smallest unit of the asset, unless solvency mode hides it or
+    /// the attestation covers several assets.
+    pub fn total(&self) -> Option<u128> {
+        let parse = |total: &str| total.split('u').next()?.parse::<u128>().ok();
+        match self {
+            AttestationResult::Total(total)
+            | AttestationResult::PrivateTotal(total)
+            | AttestationResult::TokenTotal { total, .. } => parse(total),
+            AttestationResult::ExternalTotals(_) => match self.totals().as_slice() {
+                [(_, total)] => Some(*total),
+                _ => None,
+            },
+            // Each part fits a u64, but their sum may not.
+            AttestationResult::Breakdown { liquid, bonded, unbonding } => Some(*liquid as u128 + *bonded as u128 + *unbonding as u128),
+            AttestationResult::Solvency { .. } => None,
+        }
+    }
+
+    /// The attested total of each asset, adding up the sources of the same asset.
+    pub fn totals(&self) -> Vec<(String, u128)> {
+        match self {
+            AttestationResult::ExternalTotals(totals) => {
+                let mut sums = BTreeMap::<String, u128>::new();
+                for (asset, total) in totals {
+                    if let Ok(total) = total.trim_end_matches("u128").parse::<u128>() {
+                        let sum = sums.entry(asset.clone()).or_default();
+                        *sum = sum.saturating_add(total);
+                    }
+                }
+                sums.into_iter().collect()
+            }
+            _ => self.total().map(|total| vec![(self.asset(), total)]).unwrap_or_default(),
+        }
+    }
+}
+
+impl std::fmt::Display for AttestationResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttestationResult::Total(total) | AttestationResult::PrivateTotal(total) => write!(f, "{}", total),
+            AttestationResult::ExternalTotals(_) => write!(
+                f,
+                "{}",
+                self.totals().iter().map(|(asset, total)| format!("{} {}", total, asset)).collect::<Vec<_>>().join(", ")
+            ),
+            AttestationResult::TokenTotal { token_id, total } => write!(f, "{} of token {}", total, token_id),
+            AttestationResult::Breakdown { liquid, bonded, unbonding } => write!(
+                f,
+                "{} (liquid {}, bonded {}, unbonding {})",
+                *liquid as u128 + *bonded as u128 + *unbonding as u128, liquid, bonded, unbonding
+            ),
+            AttestationResult::Solvency { threshold, solvent: true } => write!(f, "PASS (reserves >= {})", threshold),
+            AttestationResult::Solvency { threshold, solvent: false } => write!(f, "FAIL (reserves < {})", threshold),
+        }
+    }
+}
+
+/// The details of an attestation, as resolved from the endpoint.
+pub struct AttestationDetails {
+    pub height: String,
+    pub timestamp: String,
+    pub unix_timestamp: i64,
+    pub data_hash: String,
+    pub result: AttestationResult,
+    /// The mapping entry the result was read from.
+    pub entry: MappingEntry,
+    pub transaction: ConfirmedTransaction<CurrentNetwork>,
+}
+
+/// A mapping entry, with the key and value in their Aleo plaintext form.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct MappingEntry {
+    pub program_id: String,
+    pub mapping: String,
+    pub key: String,
+    pub value: String,
+}
+
+/// A utility to resolve the height, timestamp, data hash and result of an attestation.
+pub fn get_attestation_details(
+    tx_id: &str,
+    private_key: &str,
+    endpoint: &str,
+    network: &str,
+) -> Result<AttestationDetails> {
+    let height = get_block_height(tx_id, endpoint, network)?;
+    let unix_timestamp = get_block_timestamp(&height, endpoint, network)?;
+    let timestamp = chrono::DateTime::from_timestamp(unix_timestamp, 0)
+        .ok_or_else(|| anyhow!("Invalid block timestamp {}", unix_timestamp))?
+        .to_string();
+    let transaction = get_confirmed_transaction::<CurrentNetwork>(tx_id, endpoint, network)?;
+    // Pull out the data hash from the first argument of the future in the output of the root transition,
+    // which comes after the transitions of any calls it makes.
+    let transition = transaction.execution()
+        .and_then(|execution| execution.transitions().last())
+        .ok_or_else(|| anyhow!("Transaction {} has no transitions", tx_id))?;
+    let arguments = match transition.outputs().first() {
+        Some(Output::Future(_, Some(future))) => future.arguments(),
+        _ => return Err(anyhow!("Could not find data hash.")),
+    };
+    let data_hash = match arguments.first() {
+        Some(Argument::Plaintext(plaintext)) => plaintext.to_string(),
+        _ => return Err(anyhow!("Could not find data hash.")),
+    };
+    // Construct a query for the result at that point in time.
+    let address = Address::<CurrentNetwork>::try_from(PrivateKey::<CurrentNetwork>::from_str(private_key)?)?;
+    let raw_string = format!("{{user:{address},hash:{data_hash},height:{height}u32}}");
+    let function_name = transition.function_name().to_string();
+    let (program_id, mapping, key) = if transition.program_id().to_string() == SOLVENCY_PROGRAM_ID {
+        // The threshold is the last argument of the solvency future, and part of the key.
+        let threshold = match arguments.last() {
+            Some(Argument::Plaintext(plaintext)) => plaintext.to_string(),
+            _ => return Err(anyhow!("Could not find solvency threshold.")),
+        };
+        (SOLVENCY_PROGRAM_ID, "solvency", format!("{{user:{address},hash:{data_hash},height:{height}u32,threshold:{threshold}}}"))
+    } else if transition.program_id().to_string() == TOKEN_PROGRAM_ID {
+        // The token ID is the last argument of the token future, and part of the key.
+        let token_id = match arguments.last() {
+            Some(Argument::Plaintext(plaintext)) => plaintext.to_string(),
+            _ => return Err(anyhow!("Could not find token ID.")),
+        };
+        (TOKEN_PROGRAM_ID, "token_data", format!("{{user:{address},token_id:{token_id},hash:{data_hash},height:{height}u32}}"))
+    } else if function_name == "record_external_balances" {
+        (PROGRAM_ID, "external_data", raw_string)
+    } else if function_name.starts_with("record_stake_balances") {
+        (PROGRAM_ID, "stake_data", raw_string)
+    } else if function_name.starts_with("record_private_balances") {
+        (PROGRAM_ID, "private_data", raw_string)
+    } else if function_name == "record_liabilities" {
+        (PROGRAM_ID, "liabilities", raw_string)
+    } else if transition.program_id().to_string() == LEGACY_PROGRAM_ID {
+        (LEGACY_PROGRAM_ID, "data", raw_string)
+    } else {
+        (PROGRAM_ID, "data", raw_string)
+    };
+    let value = get_mapping_value(program_id, mapping, key.as_str(), endpoint, network)?;
+
+    let result = match mapping {
+        "solvency" => {
+            // The threshold is the last argument of the solvency future.
+            let threshold = match arguments.last() {
+                Some(Argument::Plaintext(plaintext)) => plaintext.to_string(),
+                _ => return Err(anyhow!("Could not find solvency threshold.")),
+            };
+            AttestationResult::Solvency { threshold, solvent: value == "true" }
+        }
+        "token_data" => match arguments.last() {
+            Some(Argument::Plaintext(token_id)) => AttestationResult::TokenTotal { token_id: token_id.to_string(), total: value.clone() },
+            _ => return Err(anyhow!("Could not find token ID.")),
+        },
+        "external_data" => {
+            // Unused entries of the batch are all zeros.
+            let entries = match Plaintext::<CurrentNetwork>::from_str(&value)? {
+                Plaintext::Array(entries, _) => entries,
+                other => return Err(anyhow!("Expected an array of external totals, found '{}'", other)),
+            };
+            let mut totals = Vec::new();
+            for entry in &entries {
+                let asset = match entry {
+                    Plaintext::Struct(members, _) => match members.get(&Identifier::from_str("asset")?) {
+                        Some(Plaintext::Literal(Literal::Field(asset), _)) => *asset,
+                        _ => return Err(anyhow!("Missing asset in external total '{}'", entry)),
+                    },
+                    _ => return Err(anyhow!("Expected a struct, found '{}'", entry)),
+                };
+                if asset != Field::zero() {
+                    totals.push((asset_name(&asset), u128_member(entry, "total")?.to_string()));
+                }
+            }
+            AttestationResult::ExternalTotals(totals)
+        }
+        "stake_data" => {
+            let balances = Plaintext::<CurrentNetwork>::from_str(&value)?;
+            AttestationResult::Breakdown {
+                liquid: u64_member(&balances, "liquid")?,
+                bonded: u64_member(&balances, "bonded")?,
+                unbonding: u64_member(&balances, "unbonding")?,
+            }
+        }
+        "private_data" => AttestationResult::PrivateTotal(value.clone()),
+        _ => AttestationResult::Total(value.clone()),
+    };
+    let entry = MappingEntry { program_id: program_id.to_string(), mapping: mapping.to_string(), key, value };
+
+    Ok(AttestationDetails { height, timestamp, unix_timestamp, data_hash, result, entry, transaction })
+}
+
+/// A utility to read a `u128` member of a struct mapping value.
+fn u128_member(value: &Plaintext<CurrentNetwork>, name: &str) -> Result<u128> {
+    match value {
+        Plaintext::Struct(members, _) => match members.get(&Identifier::from_str(name)?) {
+            Some(Plaintext::Literal(Literal::U128(member), _)) => Ok(**member),
+            _ => Err(anyhow!("Missing u128 member '{}'", name)),
+        },
+        _ => Err(anyhow!("Expected a struct, found '{}'", value)),
+    }
+}
+
+/// A utility to read a `u64` member of a struct mapping value.
+fn u64_member(value: &Plaintext<CurrentNetwork>, name: &str) -> Result<u64> {
+    match value {
+        Plaintext::Struct(members, _) => match members.get(&Identifier::from_str(name)?) {
+            Some(Plaintext::Literal(Literal::U64(member), _)) => Ok(**member),
+            _ => Err(anyhow!("Missing u64 member '{}'", name)),
+        },
+        _ => Err(anyhow!("Expected a struct, found '{}'", value)),
+    }
+}
+
+/// A utility to get the latest block height.
+pub fn get_latest_height(
+    endpoint: &str,
+    network: &str,
+) -> Result<u32> {
+    // Construct a URL to get the latest block height.
+    let url = format!("{}/{}/block/height/latest", endpoint, network);
+    latest_height(ureq::get(&url))
+}
+
+/// A utility to get the latest block height, giving up on an endpoint that does not answer
+/// within the timeout.
+pub fn get_latest_height_within(
+    endpoint: &str,
+    network: &str,
+    timeout: Duration,
+) -> Result<u32> {
+    let url = format!("{}/{}/block/height/latest", endpoint, network);
+    latest_height(ureq::get(&url).timeout(timeout))
+}
+
+fn latest_height(request: ureq::Request) -> Result<u32> {
+    // The response should be JSON with the height.
+    let response = timed_call("/block/height/latest", request)
+        .map_err(|e| anyhow!("Failed to get latest height: {}", e))?;
+    // Get the height from the response.
+    let height = response.into_json::<u32>()
+        .map_err(|e| anyhow!("Failed to get latest height: {}", e))?;
+
+    Ok(height)
+}
+
+/// A utility to get the blocks in the range `[start, end)`.
+pub fn get_blocks<N: Network>(
+    start: u32,
+    end: u32,
+    endpoint: &str,
+    network: &str,
+) -> Result<Vec<Block<N>>> {
+    // Construct a URL to get the blocks in the range.
+    let url = format!("{}/{}/blocks?start={}&end={}", endpoint, network, start, end);
+    // Get the blocks in the range.
+    // The response should be JSON with a list of block objects.
+    let response = timed_call("/blocks", ureq::get(&url))
+        .map_err(|e| anyhow!("Failed to get blocks: {}", e))?;
+    // Get the block objects from the response.
+    let blocks = response.into_json::<Vec<Block<N>>>()
+        .map_err(|e| anyhow!("Failed to get blocks: {}", e))?;
+
+    Ok(blocks)
+}
+
+/// A utility to find the transition that consumed an input, such as a record serial number.
+/// Returns `None` if no transition has consumed it.
+pub fn find_transition_id(
+    input_id: &str,
+    endpoint: &str,
+    network: &str,
+) -> Result<Option<String>> {
+    // Construct a URL to find the transition ID from the input ID.
+    let url = format!("{}/{}/find/transitionID/{}", endpoint, network, input_id);
+    // Find the transition ID.
+    // The response should be JSON with the transition ID, or a 404 if it is not found.
+    let start = Instant::now();
+    let response = ureq::get(&url).call();
+    // A 404 is the expected answer for an unspent input, not an endpoint error.
+    let success = matches!(response, Ok(_) | Err(ureq::Error::Status(404, _)));
+    METRICS.observe_request("/find/transitionID", start.elapsed(), success);
+    let response = match response {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, _)) => return Ok(None),
+        Err(e) => return Err(anyhow!("Failed to find transition ID: {}", e)),
+    };
+    // Get the transition ID from the response.
+    let transition_id = response.into_json::<serde_json::Value>()
+        .map_err(|e| anyhow!("Failed to find transition ID: {}", e))?.to_string().replace("\"", "");
+
+    Ok(Some(transition_id))
+}
+
+/// A utility to get the source of a deployed program.
+pub fn get_program(
+    program_id: &str,
+    endpoint: &str,
+    network: &str,
+) -> Result<String> {
+    // Construct a URL to get the program.
+    let url = format!("{}/{}/program/{}", endpoint, network, program_id);
+    // Get the program.
+    // The response should be JSON with the program source as a string.
+    let response = timed_call("/program", ureq::get(&url))
+        .map_err(|e| anyhow!("Failed to get program: {}", e))?;
+    // Get the program source from the response.
+    let program = response.into_json::<String>()
+        .map_err(|e| anyhow!("Failed to get program: {}", e))?;
+
+    Ok(program)
+}
@@ -0,0 +1,193 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::time::{interval, Duration};
+use tracing::{error, info, warn};
+
+use crate::state::AppState;
+use crate::storage::save_outbox;
+
+/// How often the outbox is checked for deliveries that are due.
+const DELIVERY_INTERVAL: Duration = Duration::from_secs(5);
+/// The first retry waits this long, doubling with each failed attempt.
+const BASE_BACKOFF_SECS: i64 = 10;
+/// Retries never wait longer than an hour, and are kept until they succeed.
+const MAX_BACKOFF_SECS: i64 = 3600;
+/// How long connecting to a webhook may take.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a webhook may take to answer once connected.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An outgoing webhook, as configured in the config file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Key for the HMAC-SHA256 signature in the `X-Signature-256` header.
+    pub secret: String,
+    /// The events to send, by name; all events if empty.
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+/// An event sent to the webhooks.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// An attestation transaction was broadcast to the endpoint.
+    AttestationBroadcast { transaction: String, function: String },
+    /// An attestation transaction was accepted in a block.
+    AttestationAccepted { transaction: String, height: u32 },
+    /// An attestation transaction was rejected, or never confirmed.
+    AttestationRejected { transaction: String, reason: String },
+    /// A run, or part of one, failed before broadcasting.
+    ProvingFailed { run_id: String, error: String },
+    /// Addresses were added to or removed from the tracked set.
+    AddressSetChanged { added: Vec<String>, removed: Vec<String>, tracked: usize },
+    /// An alert rule fired.
+    AlertRaised { alert: String, message: String },
+    /// The condition of a raised alert no longer holds.
+    AlertCleared { alert: String, message: String },
+}
+
+impl WebhookEvent {
+    pub const NAMES: [&'static str; 7] = [
+        "attestation_broadcast",
+        "attestation_accepted",
+        "attestation_rejected",
+        "proving_failed",
+        "address_set_changed",
+        "alert_raised",
+        "alert_cleared",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebhookEvent::AttestationBroadcast { .. } => Self::NAMES[0],
+            WebhookEvent::AttestationAccepted { .. } => Self::NAMES[1],
+            WebhookEvent::AttestationRejected { .. } => Self::NAMES[2],
+            WebhookEvent::ProvingFailed { .. } => Self::NAMES[3],
+            WebhookEvent::AddressSetChanged { .. } => Self::NAMES[4],
+            WebhookEvent::AlertRaised { .. } => Self::NAMES[5],
+            WebhookEvent::AlertCleared { .. } => Self::NAMES[6],
+        }
+    }
+}
+
+/// A signed payload waiting to be delivered to a webhook.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Delivery {
+    pub id: String,
+    pub url: String,
+    pub event: String,
+    pub body: String,
+    pub signature: String,
+    pub attempts: u32,
+    /// Unix time of the next attempt.
+    pub next_attempt: i64,
+    pub last_error: Option<String>,
+}
+
+/// Queues the event for every webhook subscribed to it, and saves the outbox.
+pub async fn emit(st: &mut AppState, event: WebhookEvent) {
+    if st.webhooks.is_empty() {
+        return;
+    }
+    let id = format!("{:016x}", rand::random::<u64>());
+    let now = Utc::now();
+    let mut payload = serde_json::to_value(&event).unwrap_or_default();
+    payload["id"] = id.clone().into();
+    payload["created"] = now.to_rfc3339().into();
+    let body = payload.to_string();
+
+    for webhook in st.webhooks.iter().filter(|w| w.events.is_empty() || w.events.iter().any(|e| e == event.name())) {
+        st.outbox.push(Delivery {
+            id: id.clone(),
+            url: webhook.url.clone(),
+            event: event.name().to_string(),
+            signature: sign(&webhook.secret, &body),
+            body: body.clone(),
+            attempts: 0,
+            next_attempt: now.timestamp(),
+            last_error: None,
+        });
+    }
+    if let Err(e) = save_outbox(&st.outbox, &st.outbox_file).await {
+        error!("Failed to save webhook outbox: {}", e);
+    }
+}
+
+/// Signs the body with HMAC-SHA256, in the `sha256=<hex>` form.
+fn sign(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Delivers the outbox in the background, retrying failed deliveries with exponential backoff.
+pub fn spawn_delivery_task(app_state: Arc<RwLock<AppState>>) {
+    tokio::spawn(async move {
+        let mut ticker = interval(DELIVERY_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let now = Utc::now().timestamp();
+            let due = app_state.read().await.outbox.iter()
+                .filter(|delivery| delivery.next_attempt <= now)
+                .cloned()
+                .collect::<Vec<_>>();
+            if due.is_empty() {
+                continue;
+            }
+
+            // A webhook that hangs would otherwise hold up the deliveries behind it.
+            let agent = ureq::AgentBuilder::new()
+                .timeout_connect(CONNECT_TIMEOUT)
+                .timeout_read(READ_TIMEOUT)
+                .build();
+            let mut results = Vec::new();
+            for delivery in due {
+                let request = delivery.clone();
+                let agent = agent.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    agent.post(&request.url)
+                        .set("Content-Type", "application/json")
+                        .set("X-Webhook-Event", &request.event)
+                        .set("X-Webhook-Delivery", &request.id)
+                        .set("X-Signature-256", &request.signature)
+                        .send_string(&request.body)
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                })
+                .await
+                .unwrap_or_else(|e| Err(format!("delivery task failed: {:?}", e)));
+                results.push((delivery, result));
+            }
+
+            let mut st = app_state.write().await;
+            for (delivery, result) in results {
+                let Some(index) = st.outbox.iter().position(|d| d.id == delivery.id && d.url == delivery.url) else { continue };
+                match result {
+                    Ok(()) => {
+                        info!("Delivered {} webhook {} to {}", delivery.event, delivery.id, delivery.url);
+                        st.outbox.remove(index);
+                    }
+                    Err(e) => {
+                        let entry = &mut st.outbox[index];
+                        entry.attempts += 1;
+                        let backoff = BASE_BACKOFF_SECS.saturating_mul(1 << (entry.attempts - 1).min(16)).min(MAX_BACKOFF_SECS);
+                        entry.next_attempt = Utc::now().timestamp() + backoff;
+                        warn!("Failed to deliver {} webhook {} to {} (attempt {}, retrying in {}s): {}",
+                            entry.event, entry.id, entry.url, entry.attempts, backoff, e);
+                        entry.last_error = Some(e);
+                    }
+                }
+            }
+            if let Err(e) = save_outbox(&st.outbox, &st.outbox_file).await {
+                error!("Failed to save webhook outbox: {}", e);
+            }
+        }
+    });
+}